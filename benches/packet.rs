@@ -0,0 +1,48 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use elscan::{decoder, packet::Packet};
+use std::hint::black_box;
+
+// an INF of a smart meter with its instantaneous power and cumulative energy
+const METER_INF: &[u8] = &[
+    0x10, 0x81, 0x00, 0x00, 0x02, 0x88, 0x01, 0x05, 0xFF, 0x01, 0x73, 0x02, 0xE7, 0x04, 0x00, 0x00,
+    0x01, 0xF4, 0xE0, 0x04, 0x00, 0x01, 0xE2, 0x40,
+];
+
+// an INF of an air conditioner changing its state
+const AIRCON_INF: &[u8] = &[
+    0x10, 0x81, 0x00, 0x00, 0x01, 0x30, 0x01, 0x05, 0xFF, 0x01, 0x73, 0x03, 0x80, 0x01, 0x30, 0xB0,
+    0x01, 0x42, 0xBB, 0x01, 0x1A,
+];
+
+fn bench_parse(c: &mut Criterion) {
+    c.bench_function("parse meter INF", |b| {
+        b.iter(|| Packet::try_from(black_box(METER_INF)).unwrap())
+    });
+    c.bench_function("parse aircon INF", |b| {
+        b.iter(|| Packet::try_from(black_box(AIRCON_INF)).unwrap())
+    });
+}
+
+// what the scanner does for every notification
+fn bench_notify(c: &mut Criterion) {
+    c.bench_function("parse and decode aircon INF", |b| {
+        b.iter(|| {
+            let packet = Packet::try_from(black_box(AIRCON_INF)).unwrap();
+            let props = packet.to_props();
+            props
+                .iter()
+                .map(|(&epc, edt)| decoder::decode(packet.seoj.class(), epc, edt, &props))
+                .collect::<Vec<_>>()
+        })
+    });
+}
+
+fn bench_to_bytes(c: &mut Criterion) {
+    let packet = Packet::try_from(AIRCON_INF).unwrap();
+    c.bench_function("serialize aircon INF", |b| {
+        b.iter(|| black_box(&packet).to_bytes())
+    });
+}
+
+criterion_group!(benches, bench_parse, bench_notify, bench_to_bytes);
+criterion_main!(benches);
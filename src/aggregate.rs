@@ -0,0 +1,138 @@
+use crate::{
+    consts::epc,
+    decoder::{
+        self, Props, Value, EV_CHARGER_DISCHARGER, PV_POWER_GENERATION, SMART_METER,
+        STORAGE_BATTERY,
+    },
+    packet::EOJ,
+};
+use serde::Serialize;
+use std::{collections::HashMap, net::IpAddr, sync::Mutex};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Role {
+    // positive while buying electricity from the grid, negative while selling
+    Grid,
+    Generation,
+    // positive while charging, negative while discharging
+    Storage,
+}
+
+// the instantaneous power of the whole household in W
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HouseholdPower {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grid: Option<f64>,
+    pub generation: f64,
+    pub storage: f64,
+    // derived from the others, which is known only when a smart meter is found
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consumption: Option<f64>,
+}
+
+#[derive(Default)]
+pub struct Aggregator {
+    sources: Mutex<HashMap<(IpAddr, EOJ), (Role, f64)>>,
+}
+
+impl Aggregator {
+    // records the instantaneous power of the object if it reports one, returning the updated total
+    pub fn update(&self, addr: IpAddr, eoj: EOJ, props: &Props) -> Option<HouseholdPower> {
+        let (role, epc) = match eoj.class() {
+            SMART_METER => (Role::Grid, epc::smart_meter::INSTANTANEOUS_POWER),
+            PV_POWER_GENERATION => (
+                Role::Generation,
+                epc::pv_power_generation::INSTANTANEOUS_GENERATION,
+            ),
+            STORAGE_BATTERY | EV_CHARGER_DISCHARGER => {
+                (Role::Storage, epc::storage_battery::INSTANTANEOUS_POWER)
+            }
+            _ => return None,
+        };
+        let edt = props.get(&epc)?;
+        let Some(Value::Number(watts)) = decoder::decode(eoj.class(), epc, edt, props).value else {
+            return None;
+        };
+        let mut sources = self.sources.lock().unwrap();
+        sources.insert((addr, eoj), (role, watts));
+        Some(total(sources.values()))
+    }
+
+    // the latest total, or none until an object reports its power
+    pub fn total(&self) -> Option<HouseholdPower> {
+        let sources = self.sources.lock().unwrap();
+        (!sources.is_empty()).then(|| total(sources.values()))
+    }
+}
+
+fn total<'a>(sources: impl Iterator<Item = &'a (Role, f64)>) -> HouseholdPower {
+    let mut grid = None;
+    let mut generation = 0.0;
+    let mut storage = 0.0;
+    for &(role, watts) in sources {
+        match role {
+            Role::Grid => *grid.get_or_insert(0.0) += watts,
+            Role::Generation => generation += watts,
+            Role::Storage => storage += watts,
+        }
+    }
+    HouseholdPower {
+        grid,
+        generation,
+        storage,
+        consumption: grid.map(|grid| grid + generation - storage),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{ElU8, EDT};
+
+    #[test]
+    fn test_aggregator_update() {
+        let addr: IpAddr = "192.168.1.10".parse().unwrap();
+        let aggregator = Aggregator::default();
+        assert_eq!(aggregator.total(), None);
+
+        let aircon = Props::from([(ElU8(0x80), EDT::from(vec![0x30]))]);
+        assert_eq!(aggregator.update(addr, EOJ::new(0x0130, 1), &aircon), None);
+
+        let pv = Props::from([(ElU8(0xE0), EDT::from(vec![0x0B, 0xB8]))]);
+        assert_eq!(
+            aggregator.update(addr, EOJ::new(PV_POWER_GENERATION, 1), &pv),
+            Some(HouseholdPower {
+                grid: None,
+                generation: 3000.0,
+                storage: 0.0,
+                consumption: None,
+            })
+        );
+
+        let battery = Props::from([(ElU8(0xD3), EDT::from(vec![0x00, 0x00, 0x03, 0xE8]))]);
+        aggregator.update(addr, EOJ::new(STORAGE_BATTERY, 1), &battery);
+        let meter = Props::from([(ElU8(0xE7), EDT::from(vec![0xFF, 0xFF, 0xFC, 0x18]))]);
+        assert_eq!(
+            aggregator.update(addr, EOJ::new(SMART_METER, 1), &meter),
+            Some(HouseholdPower {
+                grid: Some(-1000.0),
+                generation: 3000.0,
+                storage: 1000.0,
+                consumption: Some(1000.0),
+            })
+        );
+
+        // a newer value replaces the previous one of the same object
+        let battery = Props::from([(ElU8(0xD3), EDT::from(vec![0xFF, 0xFF, 0xFE, 0x0C]))]);
+        assert_eq!(
+            aggregator.update(addr, EOJ::new(STORAGE_BATTERY, 1), &battery),
+            Some(HouseholdPower {
+                grid: Some(-1000.0),
+                generation: 3000.0,
+                storage: -500.0,
+                consumption: Some(2500.0),
+            })
+        );
+        assert_eq!(aggregator.total().unwrap().consumption, Some(2500.0));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{
+    decoder::Props,
+    packet::{ElU8, Packet, EDT, EOJ},
+    response,
+};
+use jiff::Timestamp;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    net::IpAddr,
+    path::Path,
+    sync::Mutex,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Change {
+    pub epc: ElU8,
+    // the value read right before the write, absent if the property could not be read
+    pub old: Option<EDT>,
+    pub new: EDT,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Outcome {
+    Accepted,
+    // the device answered SetC_SNA, echoing the properties it refused
+    Refused { epcs: Vec<ElU8> },
+    // SetI is only answered when refused, which is never waited for
+    Sent,
+    Failed { error: String },
+}
+
+impl Outcome {
+    // the properties refused by any of the instances a broadcast request is answered by
+    pub fn of<'a>(result: Result<impl IntoIterator<Item = &'a Packet>, &anyhow::Error>) -> Self {
+        let responses = match result {
+            Ok(responses) => responses,
+            Err(e) => {
+                return Self::Failed {
+                    error: format!("{:#}", e),
+                }
+            }
+        };
+        let mut epcs = vec![];
+        for error in responses.into_iter().flat_map(response::write_errors) {
+            if !epcs.contains(&error.epc()) {
+                epcs.push(error.epc());
+            }
+        }
+        if epcs.is_empty() {
+            Self::Accepted
+        } else {
+            Self::Refused { epcs }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp: Timestamp,
+    actor: &'a str,
+    addr: IpAddr,
+    eoj: EOJ,
+    esv: String,
+    changes: Vec<Change>,
+    outcome: Outcome,
+    // the SHA-256 of the previous line, empty for the first one
+    prev: &'a str,
+}
+
+struct Chain {
+    file: File,
+    prev: String,
+}
+
+// an append-only JSONL file of the writes sent to the devices, apart from the logs. every line
+// carries the hash of the previous one, so that editing or removing a line breaks the chain
+pub struct Audit {
+    actor: String,
+    chain: Mutex<Chain>,
+}
+
+impl Audit {
+    // continues the chain of an existing file
+    pub fn open(path: &Path, actor: impl Into<String>) -> anyhow::Result<Self> {
+        let last = match File::open(path) {
+            Ok(f) => BufReader::new(f)
+                .lines()
+                .filter(|l| !l.as_ref().is_ok_and(|l| l.is_empty()))
+                .last()
+                .transpose()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            actor: actor.into(),
+            chain: Mutex::new(Chain {
+                file,
+                prev: last.as_deref().map(digest).unwrap_or_default(),
+            }),
+        })
+    }
+
+    pub fn record(
+        &self,
+        addr: IpAddr,
+        request: &Packet,
+        old: &Props,
+        outcome: Outcome,
+    ) -> anyhow::Result<()> {
+        let mut chain = self.chain.lock().unwrap();
+        let record = Record {
+            timestamp: Timestamp::now(),
+            actor: &self.actor,
+            addr,
+            eoj: request.deoj,
+            esv: format!("{:?}", request.esv),
+            changes: request
+                .props
+                .iter()
+                .map(|p| Change {
+                    epc: p.epc,
+                    old: old.get(&p.epc).cloned(),
+                    new: p.edt.clone(),
+                })
+                .collect(),
+            outcome,
+            prev: &chain.prev,
+        };
+        let line = serde_json::to_string(&record)?;
+        writeln!(chain.file, "{}", line)?;
+        // a record lost in a crash would look like a removed one
+        chain.file.sync_data()?;
+        chain.prev = digest(&line);
+        Ok(())
+    }
+}
+
+fn digest(line: &str) -> String {
+    Sha256::digest(line.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// follows the chain from the first line, returning the number of the records
+pub fn verify(reader: impl BufRead) -> anyhow::Result<usize> {
+    let mut prev = String::new();
+    let mut count = 0;
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let record: serde_json::Value = serde_json::from_str(&line)?;
+        if record["prev"].as_str() != Some(&prev) {
+            anyhow::bail!("line {} doesn't follow the previous record", i + 1);
+        }
+        prev = digest(&line);
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain() {
+        let path = std::env::temp_dir().join(format!("elscan-audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let addr: IpAddr = "192.168.1.10".parse().unwrap();
+        let aircon = EOJ::new(0x0130, 1);
+        let request =
+            Packet::new_set_request(aircon, vec![(ElU8(0x80), EDT::from(vec![0x30]))]).unwrap();
+        let old = Props::from([(ElU8(0x80), EDT::from(vec![0x31]))]);
+        Audit::open(&path, "alice")
+            .unwrap()
+            .record(addr, &request, &old, Outcome::Accepted)
+            .unwrap();
+        // reopened by the next run
+        Audit::open(&path, "alice")
+            .unwrap()
+            .record(addr, &request, &Props::new(), Outcome::Sent)
+            .unwrap();
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(verify(log.as_bytes()).unwrap(), 2);
+        let first: serde_json::Value = serde_json::from_str(log.lines().next().unwrap()).unwrap();
+        assert_eq!(first["changes"][0]["old"], "31");
+        assert_eq!(first["outcome"]["status"], "accepted");
+
+        assert!(verify(log.replacen("alice", "bob", 1).as_bytes()).is_err());
+        let second = log.lines().nth(1).unwrap();
+        assert!(verify(second.as_bytes()).is_err());
+    }
+}
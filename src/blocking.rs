@@ -0,0 +1,172 @@
+use crate::{
+    consts::ECHONET_LITE_PORT,
+    packet::{ElU16, Frame, Packet, ESV},
+    response,
+    transaction::{Matched, Transactions},
+};
+use log::{debug, warn};
+use std::{
+    io,
+    net::{IpAddr, SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+// sends requests one at a time and waits for the responses on the calling thread
+pub struct BlockingClient {
+    socket: UdpSocket,
+    port: u16,
+    timeout: Duration,
+    transactions: Transactions<()>,
+}
+
+impl BlockingClient {
+    // binds the socket to receive the responses on, such as 0.0.0.0:3610
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(addr)?,
+            port: ECHONET_LITE_PORT,
+            timeout: REQUEST_TIMEOUT,
+            transactions: Transactions::default(),
+        })
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // the port the devices listen on, which differs from the ECHONET Lite port only for simulators
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    // sends a packet without waiting for any response
+    pub fn send(&mut self, addr: IpAddr, mut packet: Packet) -> anyhow::Result<()> {
+        packet.tid = ElU16(self.transactions.allocate(Instant::now())?);
+        self.send_to(addr, &packet)
+    }
+
+    // the packets received while waiting other than the response are discarded
+    pub fn request(&mut self, addr: IpAddr, mut packet: Packet) -> anyhow::Result<Packet> {
+        let tid = self.transactions.begin(addr, (), Instant::now())?;
+        packet.tid = ElU16(tid);
+        let result = self.send_to(addr, &packet).and_then(|_| self.wait());
+        self.transactions
+            .end(addr, tid, result.is_ok(), Instant::now());
+        let mut response = result?;
+        if packet.esv == ESV::Get {
+            for anomaly in response::validate_get_response(&packet, &mut response) {
+                warn!(
+                    "[{}] Invalid response from {:?}: {:?}",
+                    addr, response.seoj, anomaly
+                );
+            }
+        }
+        Ok(response)
+    }
+
+    fn send_to(&self, addr: IpAddr, packet: &Packet) -> anyhow::Result<()> {
+        debug!("[{}] Sending {}", addr, packet);
+        self.socket
+            .send_to(&packet.to_bytes(), SocketAddr::new(addr, self.port))?;
+        Ok(())
+    }
+
+    fn wait(&mut self) -> anyhow::Result<Packet> {
+        let deadline = Instant::now() + self.timeout;
+        let mut buf = [0; 1500];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                anyhow::bail!("request timed out");
+            }
+            self.socket.set_read_timeout(Some(remaining))?;
+            let (n, from) = match self.socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    anyhow::bail!("request timed out")
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let from = from.ip().to_canonical();
+            let packet = match Frame::try_from(&buf[..n]) {
+                Ok(Frame::Specified(packet)) => packet,
+                Ok(Frame::Arbitrary { .. }) => continue,
+                Err(e) => {
+                    debug!("[{}] Failed to parse a packet: {:?}", from, e);
+                    continue;
+                }
+            };
+            if !packet.is_normal_response() && !packet.is_error_response() {
+                debug!("[{}] Ignored an unexpected packet: {}", from, packet);
+                continue;
+            }
+            // only the request being waited for is outstanding
+            match self.transactions.resolve(from, packet.tid.0) {
+                Matched::Pending(()) => return Ok(packet),
+                _ => debug!("[{}] Ignored an unexpected packet: {}", from, packet),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{consts::epc, packet::EOJ};
+    use std::thread;
+
+    #[test]
+    fn test_request() {
+        let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = device.local_addr().unwrap().port();
+        let mut client = BlockingClient::bind("127.0.0.1:0")
+            .unwrap()
+            .with_port(port)
+            .with_timeout(Duration::from_millis(500));
+        let responder = thread::spawn(move || {
+            let mut buf = [0; 1500];
+            let (n, from) = device.recv_from(&mut buf).unwrap();
+            let request = Packet::try_from(&buf[..n]).unwrap();
+            let response = |tid: u16| {
+                let mut bytes = vec![0x10, 0x81];
+                bytes.extend(tid.to_be_bytes());
+                bytes.extend([
+                    0x01, 0x30, 0x01, 0x05, 0xFF, 0x01, 0x72, 0x01, 0x80, 0x01, 0x30,
+                ]);
+                bytes
+            };
+            // an answer to another request comes first
+            device
+                .send_to(&response(request.tid.0.wrapping_add(1)), from)
+                .unwrap();
+            device.send_to(&response(request.tid.0), from).unwrap();
+        });
+        let packet =
+            Packet::new_get_request(EOJ::new(0x0130, 1), &[epc::OPERATION_STATUS]).unwrap();
+        let response = client
+            .request("127.0.0.1".parse().unwrap(), packet)
+            .unwrap();
+        responder.join().unwrap();
+        assert_eq!(response.esv, ESV::GetRes);
+        assert_eq!(response.seoj, EOJ::new(0x0130, 1));
+
+        let packet =
+            Packet::new_get_request(EOJ::new(0x0130, 1), &[epc::OPERATION_STATUS]).unwrap();
+        assert!(client
+            .request("127.0.0.1".parse().unwrap(), packet)
+            .is_err());
+    }
+}
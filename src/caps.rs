@@ -0,0 +1,144 @@
+use crate::{
+    client::Client,
+    decoder,
+    packet::{ElU8, Packet, EOJ},
+    response::SyncResponse,
+    scan,
+};
+use log::warn;
+use std::{fmt::Write, net::IpAddr};
+
+// what the object supports of a property, by the three property maps
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capability {
+    pub epc: ElU8,
+    pub get: bool,
+    pub set: bool,
+    pub anno: bool,
+}
+
+// the properties in any of the maps, ordered by EPC
+pub fn matrix(sync: &SyncResponse) -> Vec<Capability> {
+    let mut epcs: Vec<ElU8> = sync
+        .get_props
+        .iter()
+        .chain(&sync.set_props)
+        .chain(&sync.anno_props)
+        .copied()
+        .collect();
+    epcs.sort_by_key(|epc| epc.0);
+    epcs.dedup();
+    epcs.into_iter()
+        .map(|epc| Capability {
+            epc,
+            get: sync.get_props.contains(&epc),
+            set: sync.set_props.contains(&epc),
+            anno: sync.anno_props.contains(&epc),
+        })
+        .collect()
+}
+
+// a table of the properties with their read, write and notify flags, the names padded to line up
+pub fn render(class: u16, caps: &[Capability]) -> String {
+    let names: Vec<&str> = caps
+        .iter()
+        .map(|c| decoder::lookup(class, c.epc).map_or("Unknown property", |d| d.localized_name()))
+        .collect();
+    let width = names
+        .iter()
+        .map(|n| n.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Property".len());
+    let mut s = String::new();
+    writeln!(s, "EPC  {:<width$}  R W N", "Property").unwrap();
+    for (c, name) in caps.iter().zip(names) {
+        let flag = |on: bool, c: char| if on { c } else { '-' };
+        writeln!(
+            s,
+            "{:?}   {:<width$}  {} {} {}",
+            c.epc,
+            name,
+            flag(c.get, 'r'),
+            flag(c.set, 'w'),
+            flag(c.anno, 'n')
+        )
+        .unwrap();
+    }
+    s
+}
+
+// prints the matrix of the object, or of every instance of the node
+pub async fn run(client: &Client, addr: IpAddr, eoj: Option<EOJ>) -> anyhow::Result<()> {
+    let eojs = match eoj {
+        Some(eoj) => vec![eoj],
+        None => scan::instances(client, addr).await?,
+    };
+    for (i, target) in eojs.iter().copied().enumerate() {
+        let result = async {
+            let res = client
+                .request(addr, Packet::new_sync_request(target))
+                .await?;
+            SyncResponse::try_from(&res)
+        }
+        .await;
+        let sync = match result {
+            Ok(sync) => sync,
+            Err(e) if eoj.is_some() => return Err(e),
+            Err(e) => {
+                warn!(
+                    "[{}] Failed to read the property maps of {:?}: {:?}",
+                    addr, target, e
+                );
+                continue;
+            }
+        };
+        if i > 0 {
+            println!();
+        }
+        let class = decoder::class_name(target.class()).unwrap_or("Unknown class");
+        println!("{} {} ({})", addr, target, class);
+        print!("{}", render(target.class(), &matrix(&sync)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{ElU16, Prop, EDT, ESV};
+
+    #[test]
+    fn test_matrix() {
+        let props = [
+            (0x82, vec![0x00, 0x00, 0x52, 0x00]),
+            (0x9D, vec![0x02, 0x80, 0xB0]),
+            (0x9E, vec![0x02, 0xB0, 0x80]),
+            (0x9F, vec![0x03, 0x80, 0xB0, 0xBB]),
+        ];
+        let res = Packet {
+            tid: ElU16(1),
+            seoj: EOJ::new(0x0130, 1),
+            deoj: EOJ::new(0x05FF, 1),
+            esv: ESV::GetRes,
+            opc: ElU8(props.len() as u8),
+            props: props
+                .into_iter()
+                .map(|(epc, edt)| Prop {
+                    epc: ElU8(epc),
+                    pdc: ElU8(edt.len() as u8),
+                    edt: EDT::from(edt),
+                })
+                .collect(),
+        };
+        let sync = SyncResponse::try_from(&res).unwrap();
+        let caps = matrix(&sync);
+        assert_eq!(
+            render(0x0130, &caps),
+            "EPC  Property                            R W N\n\
+             80   Operation status                    r w n\n\
+             B0   Operation mode setting              r w n\n\
+             BB   Measured value of room temperature  r - -\n"
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{
+    client::Client,
+    consts::{eoj, epc},
+    packet::{Packet, EOJ},
+};
+use std::{fmt, net::IpAddr, str::FromStr, sync::Arc, time::Duration};
+use tokio::{task::JoinSet, time::Instant};
+
+// the exit codes of the monitoring plugins
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Status {
+    Ok = 0,
+    Warning = 1,
+    Critical = 2,
+    Unknown = 3,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Status::Ok => "OK",
+            Status::Warning => "WARNING",
+            Status::Critical => "CRITICAL",
+            Status::Unknown => "UNKNOWN",
+        })
+    }
+}
+
+// an object given as ADDR/EOJ, or only ADDR for the node profile of the node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Device {
+    pub addr: IpAddr,
+    pub eoj: EOJ,
+}
+
+impl FromStr for Device {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (addr, eoj) = match s.split_once('/') {
+            Some((addr, eoj)) => (addr, eoj.parse()?),
+            None => (s, eoj::NODE_PROFILE),
+        };
+        Ok(Self {
+            addr: addr.parse()?,
+            eoj,
+        })
+    }
+}
+
+impl fmt::Display for Device {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.eoj)
+    }
+}
+
+// the round-trip time of the device, or the reason it couldn't be reached
+pub type Outcome = (Device, Result<Duration, String>);
+
+// reads the operation status of every device at once
+pub async fn probe(client: Arc<Client>, devices: &[Device]) -> Vec<Outcome> {
+    let mut tasks = JoinSet::new();
+    for (i, &device) in devices.iter().enumerate() {
+        let client = Arc::clone(&client);
+        tasks.spawn(async move {
+            let started = Instant::now();
+            let packet = Packet::new_get_request(device.eoj, &[epc::OPERATION_STATUS]).unwrap();
+            let result = match client.request(device.addr, packet).await {
+                Ok(res) if res.is_normal_response() => Ok(started.elapsed()),
+                Ok(res) => Err(format!("answered with {:?}", res.esv)),
+                Err(e) => Err(format!("{:#}", e)),
+            };
+            (i, (device, result))
+        });
+    }
+    let mut outcomes = Vec::with_capacity(devices.len());
+    while let Some(res) = tasks.join_next().await {
+        if let Ok(outcome) = res {
+            outcomes.push(outcome);
+        }
+    }
+    outcomes.sort_by_key(|&(i, _)| i);
+    outcomes.into_iter().map(|(_, o)| o).collect()
+}
+
+// summarizes the outcomes into a status and a line with the round-trip times as performance data
+pub fn evaluate(outcomes: &[Outcome], warn: Duration, crit: Option<Duration>) -> (Status, String) {
+    let mut status = Status::Ok;
+    let mut problems = vec![];
+    let mut perfdata = vec![];
+    for (device, result) in outcomes {
+        match result {
+            Ok(rtt) => {
+                let level = match crit {
+                    Some(crit) if *rtt > crit => Status::Critical,
+                    _ if *rtt > warn => Status::Warning,
+                    _ => Status::Ok,
+                };
+                if level != Status::Ok {
+                    problems.push(format!("{} slow ({}ms)", device, rtt.as_millis()));
+                }
+                status = status.max(level);
+                perfdata.push(format!(
+                    "'{}'={}ms;{};{}",
+                    device,
+                    rtt.as_millis(),
+                    warn.as_millis(),
+                    crit.map(|c| c.as_millis().to_string()).unwrap_or_default()
+                ));
+            }
+            Err(reason) => {
+                problems.push(format!("{} unreachable ({})", device, reason));
+                status = Status::Critical;
+            }
+        }
+    }
+    let summary = if problems.is_empty() {
+        format!("{} devices reachable", outcomes.len())
+    } else {
+        problems.join(", ")
+    };
+    let line = format!("ELSCAN {} - {}", status, summary);
+    if perfdata.is_empty() {
+        (status, line)
+    } else {
+        (status, format!("{} | {}", line, perfdata.join(" ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_from_str() {
+        assert_eq!(
+            "192.168.1.20/0288:01".parse::<Device>().unwrap(),
+            Device {
+                addr: "192.168.1.20".parse().unwrap(),
+                eoj: EOJ::new(0x0288, 1)
+            }
+        );
+        assert_eq!("fe80::1".parse::<Device>().unwrap().eoj, eoj::NODE_PROFILE);
+        assert!("192.168.1.20/".parse::<Device>().is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let meter: Device = "192.168.1.20/smart-meter:1".parse().unwrap();
+        let battery: Device = "192.168.1.21/battery:1".parse().unwrap();
+        let warn = Duration::from_millis(200);
+        assert_eq!(
+            evaluate(&[(meter, Ok(Duration::from_millis(45)))], warn, None),
+            (
+                Status::Ok,
+                "ELSCAN OK - 1 devices reachable | '192.168.1.20/0288:01'=45ms;200;".to_string()
+            )
+        );
+        assert_eq!(
+            evaluate(&[(meter, Ok(Duration::from_millis(250)))], warn, None).0,
+            Status::Warning
+        );
+        assert_eq!(
+            evaluate(
+                &[(meter, Ok(Duration::from_millis(600)))],
+                warn,
+                Some(Duration::from_millis(500))
+            )
+            .0,
+            Status::Critical
+        );
+        assert_eq!(
+            evaluate(
+                &[
+                    (meter, Ok(Duration::from_millis(250))),
+                    (battery, Err("request timed out".to_string()))
+                ],
+                warn,
+                None
+            ),
+            (
+                Status::Critical,
+                "ELSCAN CRITICAL - 192.168.1.20/0288:01 slow (250ms), 192.168.1.21/027D:01 unreachable (request timed out) | '192.168.1.20/0288:01'=250ms;200;".to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,796 @@
+#[cfg(feature = "audit")]
+use crate::{
+    audit::{Audit, Outcome},
+    decoder::Props,
+};
+use crate::{
+    consts::{eoj, epc, ECHONET_LITE_PORT},
+    diagnosis::{self, Diagnosis, Timeout},
+    filter::AddrFilter,
+    limit::{Limiter, RateLimit, Verdict},
+    output::{Event, Output},
+    packet::{ElU16, Frame, Packet, EOJ, ESV},
+    response::{self, DiscoveryResponse},
+    socket::Unicast,
+    stats::{ParseError, Stats},
+    transaction::{Matched, Transactions},
+};
+use log::{debug, error, info, trace, warn};
+use serde::Serialize;
+#[cfg(feature = "audit")]
+use std::iter;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV6},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::{
+    sync::{mpsc, Semaphore},
+    time::{self, Duration, Instant},
+};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+// the instances answering a broadcast request are waited for until none has answered for this while
+const BROADCAST_SETTLE: Duration = Duration::from_millis(500);
+// how long a host is given to answer the discovery request of a diagnosis
+const DIAGNOSIS_WAIT: Duration = Duration::from_secs(1);
+// the hosts heard from by a diagnosis are reused for the other requests timing out meanwhile
+const DIAGNOSIS_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PoolStats {
+    pub max_outstanding: usize,
+    pub outstanding: usize,
+    pub peak_outstanding: usize,
+    // number of the requests which had to wait for a slot
+    pub queued: u64,
+}
+
+// sends requests and matches the received responses to them by TID
+pub struct Client {
+    sockets: Unicast,
+    filter: AddrFilter,
+    write_interval: Duration,
+    // the responses go to the waiters with their sources, which differ from the destinations of
+    // the multicast requests
+    transactions: Mutex<Transactions<mpsc::UnboundedSender<(IpAddr, Packet)>>>,
+    stale_responses: AtomicU64,
+    next_write: Mutex<HashMap<IpAddr, Instant>>,
+    // number of the arbitrary message format frames received from each node
+    arbitrary_frames: Mutex<HashMap<IpAddr, u64>>,
+    // bounds the number of the outstanding requests, the others waiting for a slot
+    pool: Semaphore,
+    max_outstanding: usize,
+    queued: AtomicU64,
+    peak_outstanding: AtomicUsize,
+    // interfaces the link-local IPv6 addresses were seen on, which are needed to send to them
+    scopes: Mutex<HashMap<Ipv6Addr, u32>>,
+    stats: Stats,
+    // reports the deviations from the specification which are tolerated anyway
+    strict: bool,
+    limiter: Option<Mutex<Limiter>>,
+    // where the sources muted by the limiter are reported
+    output: Option<Arc<Output>>,
+    #[cfg(feature = "audit")]
+    audit: Option<Audit>,
+    // when each host last answered a unicast request
+    answered: Mutex<HashMap<IpAddr, Instant>>,
+    // the hosts the latest diagnosis heard from
+    probe: tokio::sync::Mutex<Option<(Instant, HashSet<IpAddr>)>>,
+}
+
+impl Client {
+    pub fn new(
+        sockets: Unicast,
+        filter: AddrFilter,
+        write_interval: Duration,
+        max_outstanding: usize,
+        strict: bool,
+    ) -> Self {
+        Self {
+            sockets,
+            filter,
+            write_interval,
+            transactions: Mutex::new(Transactions::default()),
+            stale_responses: AtomicU64::new(0),
+            next_write: Mutex::new(HashMap::new()),
+            arbitrary_frames: Mutex::new(HashMap::new()),
+            pool: Semaphore::new(max_outstanding),
+            max_outstanding,
+            queued: AtomicU64::new(0),
+            peak_outstanding: AtomicUsize::new(0),
+            scopes: Mutex::new(HashMap::new()),
+            stats: Stats::default(),
+            strict,
+            limiter: None,
+            output: None,
+            #[cfg(feature = "audit")]
+            audit: None,
+            answered: Mutex::new(HashMap::new()),
+            probe: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    // limits the packets received from each source
+    pub fn with_rate_limit(mut self, limit: RateLimit) -> Self {
+        self.limiter = Some(Mutex::new(Limiter::new(limit)));
+        self
+    }
+
+    // reports the sources muted and unmuted by the rate limit as events
+    pub fn with_output(mut self, output: Arc<Output>) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    // records every write in the audit log, reading the values it replaces beforehand
+    #[cfg(feature = "audit")]
+    pub fn with_audit(mut self, audit: Audit) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    pub fn multicast_addrs(&self) -> Vec<IpAddr> {
+        self.sockets.multicast_addrs()
+    }
+
+    // sends a packet without waiting for any response
+    pub async fn send(&self, addr: IpAddr, mut packet: Packet) -> anyhow::Result<()> {
+        #[cfg(feature = "audit")]
+        let old = self.read_before_write(addr, &packet).await;
+        self.throttle(addr, &packet).await;
+        packet.tid = ElU16(
+            self.transactions
+                .lock()
+                .unwrap()
+                .allocate(Instant::now().into_std())?,
+        );
+        debug!("[{}] Sending {}", addr, packet);
+        let result = self.send_to(addr, &packet).await;
+        #[cfg(feature = "audit")]
+        if let Some(old) = old {
+            let outcome = match &result {
+                Ok(()) => Outcome::Sent,
+                Err(e) => Outcome::Failed {
+                    error: format!("{:#}", e),
+                },
+            };
+            self.audit(addr, &packet, &old, outcome);
+        }
+        result
+    }
+
+    pub async fn request(&self, addr: IpAddr, packet: Packet) -> anyhow::Result<Packet> {
+        self.request_within(addr, packet, REQUEST_TIMEOUT).await
+    }
+
+    // the timeout starts once the request is sent, excluding the time queued in the pool
+    pub async fn request_within(
+        &self,
+        addr: IpAddr,
+        mut packet: Packet,
+        timeout: Duration,
+    ) -> anyhow::Result<Packet> {
+        #[cfg(feature = "audit")]
+        let old = self.read_before_write(addr, &packet).await;
+        let result = self
+            .transact(addr, &mut packet, timeout, false)
+            .await
+            .map(|mut responses| responses.remove(0));
+        #[cfg(feature = "audit")]
+        if let Some(old) = old {
+            self.audit(
+                addr,
+                &packet,
+                &old,
+                Outcome::of(result.as_ref().map(iter::once)),
+            );
+        }
+        result
+    }
+
+    // a request to instance 0 of a class addresses every instance of the class on the node, whose
+    // responses are collected by their SEOJs until no more come. the other requests are answered by
+    // the one instance
+    pub async fn request_each(
+        &self,
+        addr: IpAddr,
+        mut packet: Packet,
+    ) -> anyhow::Result<BTreeMap<EOJ, Packet>> {
+        let broadcast = packet.deoj.instance() == 0;
+        #[cfg(feature = "audit")]
+        let old = self.read_before_write(addr, &packet).await;
+        let result = self
+            .transact(addr, &mut packet, REQUEST_TIMEOUT, broadcast)
+            .await
+            .map(|responses| {
+                let mut by_eoj = BTreeMap::new();
+                for response in responses {
+                    // an instance answering twice, e.g. to a retransmission
+                    by_eoj.entry(response.seoj).or_insert(response);
+                }
+                by_eoj
+            });
+        #[cfg(feature = "audit")]
+        if let Some(old) = old {
+            self.audit(
+                addr,
+                &packet,
+                &old,
+                Outcome::of(result.as_ref().map(|r| r.values())),
+            );
+        }
+        result
+    }
+
+    // sends the request and waits for the response, or for the responses of all the instances it
+    // is broadcast to, returning at least one
+    async fn transact(
+        &self,
+        addr: IpAddr,
+        packet: &mut Packet,
+        timeout: Duration,
+        broadcast: bool,
+    ) -> anyhow::Result<Vec<Packet>> {
+        let permit = match self.pool.try_acquire() {
+            Ok(permit) => permit,
+            Err(_) => {
+                self.queued.fetch_add(1, Ordering::Relaxed);
+                debug!(
+                    "[{}] Transaction pool is saturated, queuing a request",
+                    addr
+                );
+                self.pool.acquire().await?
+            }
+        };
+        let outstanding = self.max_outstanding - self.pool.available_permits();
+        self.peak_outstanding
+            .fetch_max(outstanding, Ordering::Relaxed);
+        self.throttle(addr, packet).await;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let tid = {
+            let mut transactions = self.transactions.lock().unwrap();
+            let now = Instant::now().into_std();
+            if broadcast {
+                transactions.begin_broadcast(addr, tx, now)?
+            } else {
+                transactions.begin(addr, tx, now)?
+            }
+        };
+        packet.tid = ElU16(tid);
+
+        debug!("[{}] Sending {}", addr, packet);
+        let result = async {
+            self.send_to(addr, packet).await?;
+            let mut responses = vec![];
+            let timeout_at = Instant::now() + timeout;
+            let mut deadline = timeout_at;
+            loop {
+                match time::timeout_at(deadline, rx.recv()).await {
+                    Ok(Some((_, mut response))) => {
+                        if packet.esv == ESV::Get {
+                            for anomaly in response::validate_get_response(packet, &mut response) {
+                                warn!(
+                                    "[{}] Invalid response from {:?}: {:?}",
+                                    addr, response.seoj, anomaly
+                                );
+                            }
+                        }
+                        responses.push(response);
+                        if !broadcast {
+                            return Ok(Some(responses));
+                        }
+                        deadline = timeout_at.min(Instant::now() + BROADCAST_SETTLE);
+                    }
+                    Ok(None) => anyhow::bail!("request cancelled"),
+                    Err(_) if responses.is_empty() => return Ok(None),
+                    Err(_) => return Ok(Some(responses)),
+                }
+            }
+        }
+        .await;
+        let answered = matches!(result, Ok(Some(_)));
+        self.transactions
+            .lock()
+            .unwrap()
+            .end(addr, tid, answered, Instant::now().into_std());
+        drop(permit);
+        match result {
+            Ok(Some(responses)) => {
+                self.answered.lock().unwrap().insert(addr, Instant::now());
+                Ok(responses)
+            }
+            Ok(None) => Err(Timeout { addr }.into()),
+            Err(e) => Err(e),
+        }
+    }
+
+    // finds out whether the host which didn't answer is there at all, by whether it answers
+    // discovery requests by multicast, and then whether its node profile answers by unicast
+    pub async fn diagnose(&self, addr: IpAddr) -> Diagnosis {
+        let answers_multicast = self.probe().await.contains(&addr);
+        let answers_unicast = answers_multicast && {
+            let packet = Packet::new_get_request(eoj::NODE_PROFILE, &[epc::OPERATION_STATUS]);
+            self.request_within(addr, packet.unwrap(), DIAGNOSIS_WAIT)
+                .await
+                .is_ok()
+        };
+        let last_answer = self
+            .answered
+            .lock()
+            .unwrap()
+            .get(&addr)
+            .map(|at| at.elapsed().as_secs());
+        Diagnosis::new(
+            answers_multicast,
+            answers_unicast,
+            last_answer,
+            diagnosis::arp(addr),
+        )
+    }
+
+    // attaches what the host was found to do to a timeout, returning the other errors as they are
+    pub async fn diagnosed(&self, addr: IpAddr, e: anyhow::Error) -> anyhow::Error {
+        if e.is::<Timeout>() {
+            let diagnosis = self.diagnose(addr).await;
+            e.context(diagnosis)
+        } else {
+            e
+        }
+    }
+
+    // the hosts answering a discovery request sent to every multicast group, which is sent once
+    // for all the requests timing out meanwhile
+    async fn probe(&self) -> HashSet<IpAddr> {
+        let mut probe = self.probe.lock().await;
+        if let Some((at, answered)) = &*probe {
+            if at.elapsed() < DIAGNOSIS_TTL {
+                return answered.clone();
+            }
+        }
+        // the responses are taken by the transactions, so that they aren't taken for a discovery
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut tids = vec![];
+        for group in self.multicast_addrs() {
+            let tx = tx.clone();
+            let now = Instant::now().into_std();
+            let Ok(tid) = self
+                .transactions
+                .lock()
+                .unwrap()
+                .begin_broadcast(group, tx, now)
+            else {
+                continue;
+            };
+            let mut packet = Packet::new_discovery_request();
+            packet.tid = ElU16(tid);
+            if let Err(e) = self.send_to(group, &packet).await {
+                debug!("[{}] Failed to send a discovery request: {:?}", group, e);
+            }
+            tids.push((group, tid));
+        }
+        time::sleep(DIAGNOSIS_WAIT).await;
+        let now = Instant::now().into_std();
+        for (group, tid) in tids {
+            self.transactions.lock().unwrap().end(group, tid, true, now);
+        }
+        // only the answers to the probe count, rather than whatever the hosts sent meanwhile
+        let mut answered = HashSet::new();
+        while let Ok((addr, packet)) = rx.try_recv() {
+            if DiscoveryResponse::try_from(&packet).is_ok() {
+                answered.insert(addr);
+            }
+        }
+        *probe = Some((Instant::now(), answered.clone()));
+        answered
+    }
+
+    // reads the properties about to be written if the writes are audited, leaving out the ones which
+    // could not be read
+    #[cfg(feature = "audit")]
+    async fn read_before_write(&self, addr: IpAddr, packet: &Packet) -> Option<Props> {
+        if self.audit.is_none() || !packet.is_write() {
+            return None;
+        }
+        // which every instance would answer with its own values
+        if packet.deoj.instance() == 0 {
+            return Some(Props::new());
+        }
+        let epcs: Vec<_> = packet.props.iter().map(|p| p.epc).collect();
+        let get = Packet::new_get_request(packet.deoj, &epcs).ok()?;
+        match Box::pin(self.request(addr, get)).await {
+            Ok(res) => Some(
+                res.to_props()
+                    .into_iter()
+                    .filter(|(_, edt)| !edt.0.is_empty())
+                    .collect(),
+            ),
+            Err(e) => {
+                warn!(
+                    "[{}] Failed to read {:?} before writing: {:?}",
+                    addr, packet.deoj, e
+                );
+                Some(Props::new())
+            }
+        }
+    }
+
+    #[cfg(feature = "audit")]
+    fn audit(&self, addr: IpAddr, packet: &Packet, old: &Props, outcome: Outcome) {
+        if let Some(audit) = &self.audit {
+            if let Err(e) = audit.record(addr, packet, old, outcome) {
+                error!("Failed to write the audit log: {:?}", e);
+            }
+        }
+    }
+
+    // sends from the socket of the family of the address
+    async fn send_to(&self, addr: IpAddr, packet: &Packet) -> anyhow::Result<()> {
+        let dest = match addr {
+            IpAddr::V4(ip) => SocketAddr::from((ip, ECHONET_LITE_PORT)),
+            IpAddr::V6(ip) => {
+                let scope = self.scopes.lock().unwrap().get(&ip).copied().unwrap_or(0);
+                SocketAddr::V6(SocketAddrV6::new(ip, ECHONET_LITE_PORT, 0, scope))
+            }
+        };
+        self.sockets
+            .get(&addr)?
+            .send_to(&packet.to_bytes(), dest)
+            .await?;
+        self.stats.sent(packet.esv);
+        Ok(())
+    }
+
+    // spaces out the writes to each host, as some devices drop the requests arriving in quick succession
+    async fn throttle(&self, addr: IpAddr, packet: &Packet) {
+        if !packet.is_write() {
+            return;
+        }
+        let at = {
+            let mut next_write = self.next_write.lock().unwrap();
+            let now = Instant::now();
+            let at = next_write.get(&addr).map_or(now, |&t| t.max(now));
+            next_write.insert(addr, at + self.write_interval);
+            at
+        };
+        time::sleep_until(at).await;
+    }
+
+    // parses a received datagram and hands it to the pending request waiting for it,
+    // returning the packet if there is none
+    pub fn receive(&self, msg: &[u8], addr: SocketAddr) -> Option<(IpAddr, Packet)> {
+        trace!("{:?} {:?}", addr, msg);
+        let ipv4 = addr.ip().to_canonical();
+        if !self.filter.permits(&ipv4) {
+            debug!("[{}] Ignored a packet from a filtered address", ipv4);
+            return None;
+        }
+        // the responses to the requests never count toward the rate, as a walk would mute the device
+        if !self.is_awaited(ipv4, msg) && !self.admit(ipv4) {
+            return None;
+        }
+        if let SocketAddr::V6(a) = addr {
+            if a.scope_id() != 0 {
+                self.scopes.lock().unwrap().insert(*a.ip(), a.scope_id());
+            }
+        }
+        match Frame::try_from(msg) {
+            Ok(Frame::Specified(packet)) => {
+                debug!("[{}] Received {}", ipv4, packet);
+                self.stats.received(ipv4, Some(packet.esv));
+                if addr.port() != ECHONET_LITE_PORT {
+                    self.nonstandard_port(ipv4, addr.port());
+                }
+                self.dispatch(ipv4, packet).map(|packet| (ipv4, packet))
+            }
+            Ok(Frame::Arbitrary { tid, payload }) => {
+                self.stats.received(ipv4, None);
+                let mut counts = self.arbitrary_frames.lock().unwrap();
+                let count = counts.entry(ipv4).or_default();
+                *count += 1;
+                // the node keeps sending them, so only the first one is worth noticing
+                if *count == 1 {
+                    info!("[{}] Ignoring arbitrary message format frames", ipv4);
+                }
+                debug!(
+                    "[{}] Received an arbitrary message format frame (TID: {:04X}, {} bytes)",
+                    ipv4,
+                    tid.0,
+                    payload.len()
+                );
+                None
+            }
+            Err(e) => {
+                self.stats.parse_error(ipv4, ParseError::classify(msg));
+                error!("[{}] Failed to parse a packet: {:?}", ipv4, e);
+                None
+            }
+        }
+    }
+
+    // whether the datagram answers an outstanding request, by the TID in its header
+    fn is_awaited(&self, addr: IpAddr, msg: &[u8]) -> bool {
+        match msg {
+            [0x10, _, hi, lo, ..] => self
+                .transactions
+                .lock()
+                .unwrap()
+                .is_pending(addr, u16::from_be_bytes([*hi, *lo])),
+            _ => false,
+        }
+    }
+
+    // drops the packets of the sources flooding the network before they cost any parsing
+    fn admit(&self, addr: IpAddr) -> bool {
+        let Some(limiter) = &self.limiter else {
+            return true;
+        };
+        let mut limiter = limiter.lock().unwrap();
+        match limiter.check(addr, Instant::now().into_std()) {
+            Verdict::Pass => true,
+            Verdict::Unmuted { dropped } => {
+                self.emit(&Event::Unmuted { addr, dropped });
+                true
+            }
+            Verdict::Muted => {
+                self.stats.muted(addr);
+                let limit = limiter.limit();
+                self.emit(&Event::Muted {
+                    addr,
+                    rate: limit.rate,
+                    burst: limit.burst,
+                    duration: limit.mute.as_secs(),
+                });
+                false
+            }
+            Verdict::Dropped => {
+                self.stats.muted(addr);
+                false
+            }
+        }
+    }
+
+    fn emit(&self, event: &Event) {
+        if let Some(output) = &self.output {
+            output.emit(event);
+        }
+    }
+
+    // some gateways answer from ephemeral ports, which doesn't matter as responses are matched by
+    // the address and TID
+    fn nonstandard_port(&self, addr: IpAddr, port: u16) {
+        if self.stats.nonstandard_port(addr, port) > 1 {
+            return;
+        }
+        if self.strict {
+            warn!(
+                "[{}] Sent from port {} rather than {}, violating the specification",
+                addr, port, ECHONET_LITE_PORT
+            );
+        } else {
+            debug!("[{}] Sent from port {}", addr, port);
+        }
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        PoolStats {
+            max_outstanding: self.max_outstanding,
+            outstanding: self.max_outstanding - self.pool.available_permits(),
+            peak_outstanding: self.peak_outstanding.load(Ordering::Relaxed),
+            queued: self.queued.load(Ordering::Relaxed),
+        }
+    }
+
+    // number of the responses which arrived after their requests timed out
+    pub fn stale_responses(&self) -> u64 {
+        self.stale_responses.load(Ordering::Relaxed)
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn arbitrary_frames(&self) -> HashMap<IpAddr, u64> {
+        self.arbitrary_frames.lock().unwrap().clone()
+    }
+
+    fn dispatch(&self, addr: IpAddr, packet: Packet) -> Option<Packet> {
+        if !packet.is_normal_response() && !packet.is_error_response() {
+            return Some(packet);
+        }
+        let matched = self
+            .transactions
+            .lock()
+            .unwrap()
+            .resolve(addr, packet.tid.0);
+        match matched {
+            Matched::Pending(tx) => {
+                // the request may have timed out in the meantime
+                let _ = tx.send((addr, packet));
+                None
+            }
+            Matched::Stale => {
+                self.stale_responses.fetch_add(1, Ordering::Relaxed);
+                debug!(
+                    "[{}] Dropped a late response to a timed-out request (TID: {:04X})",
+                    addr, packet.tid.0
+                );
+                None
+            }
+            Matched::Unexpected => Some(packet),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{ElU8, EDT};
+    use std::sync::Arc;
+    use tokio::net::UdpSocket;
+
+    // the requests are sent to the loopback address, which nothing answers but the tests
+    async fn client(write_interval: Duration) -> Client {
+        let sockets = Unicast {
+            v4: Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap()),
+            v6: None,
+        };
+        Client::new(sockets, AddrFilter::default(), write_interval, 8, false)
+    }
+
+    // a Get_Res of the operation status from an instance of the home air conditioner
+    fn get_res(tid: u16, instance: u8) -> Vec<u8> {
+        let mut msg = vec![0x10, 0x81];
+        msg.extend_from_slice(&tid.to_be_bytes());
+        msg.extend([
+            0x01, 0x30, instance, 0x05, 0xFF, 0x01, 0x72, 0x01, 0x80, 0x01, 0x30,
+        ]);
+        msg
+    }
+
+    const DEVICE: SocketAddr = SocketAddr::new(IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 3610);
+
+    #[tokio::test(start_paused = true)]
+    async fn test_request_timeout() {
+        let client = client(Duration::ZERO).await;
+        let started = Instant::now();
+        let packet = Packet::new_get_request(EOJ::new(0x0130, 1), &[ElU8(0x80)]).unwrap();
+        let e = client.request(DEVICE.ip(), packet).await.unwrap_err();
+        assert_eq!(e.downcast_ref::<Timeout>().unwrap().addr, DEVICE.ip());
+        // diagnosed only by the callers reporting it
+        assert_eq!(started.elapsed(), REQUEST_TIMEOUT);
+        // the late response is told from an unsolicited packet
+        assert!(client.receive(&get_res(1, 1), DEVICE).is_none());
+        assert_eq!(client.stale_responses.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_broadcast_settle() {
+        let client = client(Duration::ZERO).await;
+        let started = Instant::now();
+        let packet = Packet::new_get_request(EOJ::new(0x0130, 0), &[ElU8(0x80)]).unwrap();
+        let answering = async {
+            time::sleep(Duration::from_millis(100)).await;
+            client.receive(&get_res(1, 1), DEVICE);
+            time::sleep(Duration::from_millis(300)).await;
+            client.receive(&get_res(1, 2), DEVICE);
+        };
+        let (responses, ()) = tokio::join!(client.request_each(DEVICE.ip(), packet), answering);
+        assert_eq!(
+            responses.unwrap().into_keys().collect::<Vec<_>>(),
+            vec![EOJ::new(0x0130, 1), EOJ::new(0x0130, 2)]
+        );
+        assert_eq!(
+            started.elapsed(),
+            Duration::from_millis(400) + BROADCAST_SETTLE
+        );
+    }
+
+    // only the answers to its discovery count, not the other packets the hosts send meanwhile
+    #[tokio::test(start_paused = true)]
+    async fn test_probe() {
+        let client = client(Duration::ZERO).await;
+        let node: SocketAddr = "127.0.0.2:3610".parse().unwrap();
+        let answering = async {
+            time::sleep(Duration::from_millis(100)).await;
+            // an INF of the operation status
+            let mut inf = get_res(0, 1);
+            inf[10] = 0x73;
+            client.receive(&inf, DEVICE);
+            client.receive(
+                &[
+                    0x10, 0x81, 0x00, 0x01, 0x0E, 0xF0, 0x01, 0x05, 0xFF, 0x01, 0x72, 0x01, 0xD6,
+                    0x04, 0x01, 0x01, 0x30, 0x01,
+                ],
+                node,
+            );
+        };
+        let (answered, ()) = tokio::join!(client.probe(), answering);
+        assert_eq!(answered, HashSet::from([node.ip()]));
+    }
+
+    // a node answering the discovery is told apart from a filtered one by a unicast request
+    #[tokio::test(start_paused = true)]
+    async fn test_diagnose() {
+        let client = client(Duration::ZERO).await;
+        let node: SocketAddr = "127.0.0.2:3610".parse().unwrap();
+        let discovery_res = |tid: u8| {
+            [
+                0x10, 0x81, 0x00, tid, 0x0E, 0xF0, 0x01, 0x05, 0xFF, 0x01, 0x72, 0x01, 0xD6, 0x04,
+                0x01, 0x01, 0x30, 0x01,
+            ]
+        };
+        let answering = async {
+            time::sleep(Duration::from_millis(100)).await;
+            client.receive(&discovery_res(1), node);
+            time::sleep(DIAGNOSIS_WAIT).await;
+            client.receive(
+                // to the request following the discovery
+                &[
+                    0x10, 0x81, 0x00, 0x02, 0x0E, 0xF0, 0x01, 0x05, 0xFF, 0x01, 0x72, 0x01, 0x80,
+                    0x01, 0x30,
+                ],
+                node,
+            );
+        };
+        let (diagnosis, ()) = tokio::join!(client.diagnose(node.ip()), answering);
+        assert_eq!(diagnosis.reason, diagnosis::Reason::ObjectSilent);
+
+        // the node answering only the discovery, once the previous probe expired
+        time::sleep(DIAGNOSIS_TTL).await;
+        let answering = async {
+            time::sleep(Duration::from_millis(100)).await;
+            client.receive(&discovery_res(3), node);
+        };
+        let (diagnosis, ()) = tokio::join!(client.diagnose(node.ip()), answering);
+        assert_eq!(diagnosis.reason, diagnosis::Reason::UnicastBlocked);
+    }
+
+    // the responses to a walk never get the device muted, unlike the packets it sends by itself
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit_exempts_responses() {
+        let client = client(Duration::ZERO).await.with_rate_limit(RateLimit {
+            rate: 1.0,
+            burst: 1.0,
+            mute: Duration::from_secs(60),
+        });
+        let packet = Packet::new_get_request(EOJ::new(0x0130, 0), &[ElU8(0x80)]).unwrap();
+        let answering = async {
+            time::sleep(Duration::from_millis(100)).await;
+            for instance in 1..=3 {
+                client.receive(&get_res(1, instance), DEVICE);
+            }
+        };
+        let (responses, ()) = tokio::join!(client.request_each(DEVICE.ip(), packet), answering);
+        assert_eq!(responses.unwrap().len(), 3);
+        assert!(client.stats().snapshot().muted.is_empty());
+        let mut inf = get_res(0, 1);
+        inf[10] = 0x73;
+        assert!(client.receive(&inf, DEVICE).is_some());
+        assert!(client.receive(&inf, DEVICE).is_none());
+        assert_eq!(client.stats().snapshot().muted[&DEVICE.ip()], 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_throttle() {
+        let client = client(Duration::from_secs(1)).await;
+        let started = Instant::now();
+        let aircon = EOJ::new(0x0130, 1);
+        for _ in 0..3 {
+            let packet = Packet::new_set_no_response_request(
+                aircon,
+                vec![(ElU8(0x80), EDT::from(vec![0x30]))],
+            )
+            .unwrap();
+            client.send(DEVICE.ip(), packet).await.unwrap();
+        }
+        assert_eq!(started.elapsed(), Duration::from_secs(2));
+        // the reads aren't spaced out
+        let packet = Packet::new_get_request(aircon, &[ElU8(0x80)]).unwrap();
+        client.send(DEVICE.ip(), packet).await.unwrap();
+        assert_eq!(started.elapsed(), Duration::from_secs(2));
+    }
+}
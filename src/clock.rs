@@ -0,0 +1,151 @@
+use crate::{
+    client::Client,
+    consts::epc,
+    output::{Event, Output},
+    packet::{Packet, EDT, EOJ},
+    scan,
+};
+use jiff::{civil::DateTime, Unit, Zoned};
+use log::warn;
+use std::net::IpAddr;
+
+// reads the clock of the instance, or of all the instances of the node, optionally adjusting it to the host's
+pub async fn run(
+    client: &Client,
+    output: &Output,
+    addr: IpAddr,
+    eoj: Option<EOJ>,
+    set_from_host: bool,
+) -> anyhow::Result<()> {
+    let eojs = match eoj {
+        Some(eoj) => vec![eoj],
+        None => scan::instances(client, addr).await?,
+    };
+    for target in eojs {
+        let result = async {
+            if set_from_host {
+                write(client, addr, target, Zoned::now().datetime()).await?;
+            }
+            read(client, addr, target).await
+        }
+        .await;
+        match result {
+            Ok(datetime) => {
+                let host = Zoned::now().datetime().round(Unit::Second)?;
+                output.emit(&Event::Clock {
+                    addr,
+                    eoj: target,
+                    datetime,
+                    host,
+                    drift: datetime.duration_since(host).as_secs(),
+                });
+            }
+            Err(e) if eoj.is_some() => return Err(e),
+            // not all the instances of the node implement clocks
+            Err(e) => warn!(
+                "[{}] Failed to access the clock of {:?}: {:?}",
+                addr, target, e
+            ),
+        }
+    }
+    Ok(())
+}
+
+pub async fn read(client: &Client, addr: IpAddr, eoj: EOJ) -> anyhow::Result<DateTime> {
+    let res = client
+        .request(
+            addr,
+            Packet::new_get_request(eoj, &[epc::CURRENT_TIME, epc::CURRENT_DATE])?,
+        )
+        .await?;
+    if !res.is_normal_response() {
+        anyhow::bail!("clock is not available");
+    }
+    let props = res.to_props();
+    let (Some(time), Some(date)) = (props.get(&epc::CURRENT_TIME), props.get(&epc::CURRENT_DATE))
+    else {
+        anyhow::bail!("not found current time or date");
+    };
+    parse(time, date)
+}
+
+pub async fn write(
+    client: &Client,
+    addr: IpAddr,
+    eoj: EOJ,
+    datetime: DateTime,
+) -> anyhow::Result<()> {
+    let (time, date) = encode(datetime);
+    let res = client
+        .request(
+            addr,
+            Packet::new_set_request(
+                eoj,
+                vec![(epc::CURRENT_TIME, time), (epc::CURRENT_DATE, date)],
+            )?,
+        )
+        .await?;
+    if !res.is_normal_response() {
+        anyhow::bail!("clock setting is refused");
+    }
+    Ok(())
+}
+
+// the current time is represented as HH:MM and the current date as YYYY:MM:DD in binary
+fn parse(time: &EDT, date: &EDT) -> anyhow::Result<DateTime> {
+    let (&[h, m], &[y1, y2, mo, d]) = (&time.0[..], &date.0[..]) else {
+        anyhow::bail!("invalid current time or date");
+    };
+    let year = u16::from_be_bytes([y1.0, y2.0]);
+    Ok(DateTime::new(
+        year.try_into()?,
+        mo.0.try_into()?,
+        d.0.try_into()?,
+        h.0.try_into()?,
+        m.0.try_into()?,
+        0,
+        0,
+    )?)
+}
+
+fn encode(datetime: DateTime) -> (EDT, EDT) {
+    let [y1, y2] = (datetime.year() as u16).to_be_bytes();
+    (
+        EDT::from(vec![datetime.hour() as u8, datetime.minute() as u8]),
+        EDT::from(vec![y1, y2, datetime.month() as u8, datetime.day() as u8]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jiff::civil::date;
+
+    #[test]
+    fn test_parse_and_encode() {
+        let time = EDT::from(vec![0x0C, 0x1E]);
+        let day = EDT::from(vec![0x07, 0xE8, 0x01, 0x02]);
+        let datetime = date(2024, 1, 2).at(12, 30, 0, 0);
+        assert_eq!(parse(&time, &day).unwrap(), datetime);
+        assert_eq!(
+            encode(datetime.with().second(59).build().unwrap()),
+            (time, day)
+        );
+
+        assert!(parse(
+            &EDT::from(vec![0x0C]),
+            &EDT::from(vec![0x07, 0xE8, 0x01, 0x02])
+        )
+        .is_err());
+        assert!(parse(
+            &EDT::from(vec![0x0C, 0x3C]),
+            &EDT::from(vec![0x07, 0xE8, 0x01, 0x02])
+        )
+        .is_err());
+        assert!(parse(
+            &EDT::from(vec![0x0C, 0x1E]),
+            &EDT::from(vec![0x07, 0xE8, 0x02, 0x1E])
+        )
+        .is_err());
+    }
+}
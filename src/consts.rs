@@ -0,0 +1,72 @@
+use crate::packet::{ElU8, EOJ};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+pub const ECHONET_LITE_PORT: u16 = 3610;
+pub const MULTICAST_ADDR_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 23, 0);
+// the link-local all-nodes address
+pub const MULTICAST_ADDR_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1);
+
+pub mod eoj {
+    use super::*;
+    use crate::decoder;
+
+    pub const NODE_PROFILE: EOJ = EOJ::new(decoder::NODE_PROFILE, 0x01);
+    pub const CONTROLLER: EOJ = EOJ::new(decoder::CONTROLLER, 0x01);
+}
+
+pub mod epc {
+    use super::*;
+
+    // device object super class
+    pub const OPERATION_STATUS: ElU8 = ElU8(0x80);
+    pub const INSTALLATION_LOCATION: ElU8 = ElU8(0x81);
+    pub const STANDARD_VERSION_INFORMATION: ElU8 = ElU8(0x82);
+    pub const IDENTIFICATION_NUMBER: ElU8 = ElU8(0x83);
+    pub const FAULT_STATUS: ElU8 = ElU8(0x88);
+    pub const MANUFACTURER_CODE: ElU8 = ElU8(0x8A);
+    pub const PRODUCT_CODE: ElU8 = ElU8(0x8C);
+    pub const CURRENT_TIME: ElU8 = ElU8(0x97);
+    pub const CURRENT_DATE: ElU8 = ElU8(0x98);
+    pub const ANNO_PROPERTY_MAP: ElU8 = ElU8(0x9D);
+    pub const SET_PROPERTY_MAP: ElU8 = ElU8(0x9E);
+    pub const GET_PROPERTY_MAP: ElU8 = ElU8(0x9F);
+
+    // node profile
+    pub const NUMBER_OF_INSTANCES: ElU8 = ElU8(0xD3);
+    pub const NUMBER_OF_CLASSES: ElU8 = ElU8(0xD4);
+    pub const INSTANCE_LIST_NOTIFICATION: ElU8 = ElU8(0xD5);
+    pub const INSTANCE_LIST_S: ElU8 = ElU8(0xD6);
+    pub const CLASS_LIST_S: ElU8 = ElU8(0xD7);
+
+    // the class properties, whose codes overlap across the classes
+    pub mod smart_meter {
+        use super::*;
+
+        pub const COEFFICIENT: ElU8 = ElU8(0xD3);
+        pub const CUMULATIVE_ENERGY: ElU8 = ElU8(0xE0);
+        pub const ENERGY_UNIT: ElU8 = ElU8(0xE1);
+        pub const ENERGY_HISTORY: ElU8 = ElU8(0xE2);
+        pub const HISTORY_DAY: ElU8 = ElU8(0xE5);
+        pub const INSTANTANEOUS_POWER: ElU8 = ElU8(0xE7);
+    }
+
+    pub mod pv_power_generation {
+        use super::*;
+
+        pub const INSTANTANEOUS_GENERATION: ElU8 = ElU8(0xE0);
+    }
+
+    // shared by the EV charger/discharger
+    pub mod storage_battery {
+        use super::*;
+
+        pub const INSTANTANEOUS_POWER: ElU8 = ElU8(0xD3);
+    }
+
+    pub mod home_air_conditioner {
+        use super::*;
+
+        pub const SET_TEMPERATURE: ElU8 = ElU8(0xB3);
+        pub const ROOM_TEMPERATURE: ElU8 = ElU8(0xBB);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::{
+    client::Client,
+    decoder,
+    output::{Event, Output},
+    packet::{ElU8, Packet, EDT, EOJ},
+    response::{self, WriteError},
+};
+use anyhow::Context;
+use std::net::IpAddr;
+
+// parses a property assignment such as "80=30"
+pub fn parse_assignment(s: &str) -> anyhow::Result<(ElU8, EDT)> {
+    let Some((epc, edt)) = s.split_once('=') else {
+        anyhow::bail!("expected EPC=EDT");
+    };
+    let edt = EDT::from_hex(edt.strip_prefix("0x").unwrap_or(edt))?;
+    if edt.0.is_empty() {
+        anyhow::bail!("empty EDT");
+    }
+    // which PDC can't count
+    if edt.0.len() > u8::MAX as usize {
+        anyhow::bail!("EDT of {} bytes", edt.0.len());
+    }
+    Ok((epc.parse()?, edt))
+}
+
+// parses a property given by its value such as "B3=25C", which is encoded once the class is known
+pub fn parse_value(s: &str) -> anyhow::Result<(ElU8, String)> {
+    let Some((epc, value)) = s.split_once('=') else {
+        anyhow::bail!("expected EPC=VALUE");
+    };
+    if value.is_empty() {
+        anyhow::bail!("empty value");
+    }
+    Ok((epc.parse()?, value.to_string()))
+}
+
+// encodes the values for the class, failing before anything is sent if any of them doesn't fit
+pub fn encode_values(class: u16, values: &[(ElU8, String)]) -> anyhow::Result<Vec<(ElU8, EDT)>> {
+    values
+        .iter()
+        .map(|(epc, value)| {
+            let edt = decoder::encode(class, *epc, value)
+                .with_context(|| format!("invalid value {} of {:?}", value, epc))?;
+            Ok((*epc, edt))
+        })
+        .collect()
+}
+
+// an object with instance code 0 reads every instance of the class, each of which answers by itself
+pub async fn get(
+    client: &Client,
+    output: &Output,
+    addr: IpAddr,
+    eoj: EOJ,
+    epcs: &[ElU8],
+) -> anyhow::Result<()> {
+    let responses = match client
+        .request_each(addr, Packet::new_get_request(eoj, epcs)?)
+        .await
+    {
+        Ok(responses) => responses,
+        Err(e) => return Err(client.diagnosed(addr, e).await),
+    };
+    let mut failed = vec![];
+    for (&eoj, res) in &responses {
+        let props = res.to_props();
+        for (&epc, edt) in &props {
+            output.emit(&Event::Property {
+                addr,
+                eoj,
+                epc,
+                value: decoder::decode(eoj.class(), epc, edt, &props),
+            });
+        }
+        if !res.is_normal_response() {
+            failed.push(format!("{:?} of {}", unanswered(res), eoj));
+        }
+    }
+    if !failed.is_empty() {
+        anyhow::bail!("failed to get {}", failed.join(", "));
+    }
+    Ok(())
+}
+
+pub async fn set(
+    client: &Client,
+    addr: IpAddr,
+    eoj: EOJ,
+    props: Vec<(ElU8, EDT)>,
+) -> anyhow::Result<()> {
+    let responses = match client
+        .request_each(addr, Packet::new_set_request(eoj, props)?)
+        .await
+    {
+        Ok(responses) => responses,
+        Err(e) => return Err(client.diagnosed(addr, e).await),
+    };
+    let mut failed = vec![];
+    for (eoj, res) in responses.iter().filter(|(_, r)| !r.is_normal_response()) {
+        let errors: Vec<_> = response::write_errors(res)
+            .iter()
+            .map(|e| describe(eoj.class(), e))
+            .collect();
+        failed.push(format!("{}: {}", eoj, errors.join(", ")));
+    }
+    if !failed.is_empty() {
+        anyhow::bail!("failed to set {}", failed.join("; "));
+    }
+    Ok(())
+}
+
+// names the refused property, as the EPC alone tells little on the command line
+pub fn describe(class: u16, error: &WriteError) -> String {
+    match (error, decoder::lookup(class, error.epc())) {
+        (WriteError::Rejected { epc, attempted }, Some(def)) => format!(
+            "rejected {:?} ({})=0x{}",
+            epc,
+            def.localized_name(),
+            attempted.to_hex()
+        ),
+        (_, None) => error.to_string(),
+    }
+}
+
+// the device only responds to SetI when it refuses some of the properties, which is never waited for
+pub async fn set_no_confirm(
+    client: &Client,
+    addr: IpAddr,
+    eoj: EOJ,
+    props: Vec<(ElU8, EDT)>,
+) -> anyhow::Result<()> {
+    client
+        .send(addr, Packet::new_set_no_response_request(eoj, props)?)
+        .await
+}
+
+fn unanswered(res: &Packet) -> Vec<ElU8> {
+    res.props
+        .iter()
+        .filter(|p| p.pdc.0 == 0)
+        .map(|p| p.epc)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_assignment() {
+        assert_eq!(
+            parse_assignment("80=30").unwrap(),
+            (ElU8(0x80), EDT::from(vec![0x30]))
+        );
+        assert_eq!(
+            parse_assignment("0x98=0x07E80102").unwrap(),
+            (ElU8(0x98), EDT::from(vec![0x07, 0xE8, 0x01, 0x02]))
+        );
+        assert!(parse_assignment("80").is_err());
+        assert!(parse_assignment("80=").is_err());
+        assert!(parse_assignment("80=3").is_err());
+        assert!(parse_assignment(&format!("80={}", "00".repeat(255))).is_ok());
+        assert!(parse_assignment(&format!("80={}", "00".repeat(256))).is_err());
+    }
+
+    #[test]
+    fn test_encode_values() {
+        let values = [
+            parse_value("80=on").unwrap(),
+            parse_value("B3=25C").unwrap(),
+        ];
+        assert_eq!(
+            encode_values(decoder::HOME_AIR_CONDITIONER, &values).unwrap(),
+            vec![
+                (ElU8(0x80), EDT::from(vec![0x30])),
+                (ElU8(0xB3), EDT::from(vec![0x19]))
+            ]
+        );
+        let e = encode_values(
+            decoder::HOME_AIR_CONDITIONER,
+            &[parse_value("B3=99%").unwrap()],
+        )
+        .unwrap_err();
+        assert_eq!(
+            format!("{:#}", e),
+            "invalid value 99% of B3: expected a number in °C"
+        );
+        assert!(parse_value("B3").is_err());
+        assert!(parse_value("B3=").is_err());
+    }
+}
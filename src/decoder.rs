@@ -0,0 +1,1148 @@
+use crate::{
+    consts::epc::smart_meter,
+    packet::{ElU8, EDT},
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+// properties of a single object, which some decoders refer to (e.g. coefficients for scaling)
+pub type Props = BTreeMap<ElU8, EDT>;
+
+pub const TEMPERATURE_SENSOR: u16 = 0x0011;
+pub const HUMIDITY_SENSOR: u16 = 0x0012;
+pub const CO2_SENSOR: u16 = 0x001B;
+pub const NODE_PROFILE: u16 = 0x0EF0;
+pub const HOME_AIR_CONDITIONER: u16 = 0x0130;
+pub const PV_POWER_GENERATION: u16 = 0x0279;
+pub const STORAGE_BATTERY: u16 = 0x027D;
+pub const EV_CHARGER_DISCHARGER: u16 = 0x027E;
+pub const SMART_METER: u16 = 0x0288;
+pub const CONTROLLER: u16 = 0x05FF;
+
+// short names accepted in place of class codes
+static CLASS_NAMES: &[(&str, u16)] = &[
+    ("temperature-sensor", TEMPERATURE_SENSOR),
+    ("humidity-sensor", HUMIDITY_SENSOR),
+    ("co2-sensor", CO2_SENSOR),
+    ("node-profile", NODE_PROFILE),
+    ("aircon", HOME_AIR_CONDITIONER),
+    ("pv", PV_POWER_GENERATION),
+    ("battery", STORAGE_BATTERY),
+    ("ev-charger", EV_CHARGER_DISCHARGER),
+    ("smart-meter", SMART_METER),
+    ("controller", CONTROLLER),
+];
+
+// the names in the Japanese edition of the specification, which the installers are familiar with
+static CLASS_NAMES_JA: &[(u16, &str)] = &[
+    (TEMPERATURE_SENSOR, "温度センサ"),
+    (HUMIDITY_SENSOR, "湿度センサ"),
+    (CO2_SENSOR, "CO2センサ"),
+    (NODE_PROFILE, "ノードプロファイル"),
+    (HOME_AIR_CONDITIONER, "家庭用エアコン"),
+    (PV_POWER_GENERATION, "住宅用太陽光発電"),
+    (STORAGE_BATTERY, "蓄電池"),
+    (EV_CHARGER_DISCHARGER, "電気自動車充放電器"),
+    (SMART_METER, "低圧スマート電力量メータ"),
+    (CONTROLLER, "コントローラ"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    En,
+    Ja,
+}
+
+impl FromStr for Lang {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "en" => Ok(Self::En),
+            "ja" => Ok(Self::Ja),
+            _ => anyhow::bail!("unsupported language, which is either en or ja"),
+        }
+    }
+}
+
+// the language of the names for the whole process, as they are looked up deep in the decoding
+static LANG: AtomicU8 = AtomicU8::new(Lang::En as u8);
+
+pub fn set_lang(lang: Lang) {
+    LANG.store(lang as u8, Ordering::Relaxed);
+}
+
+pub fn lang() -> Lang {
+    match LANG.load(Ordering::Relaxed) {
+        1 => Lang::Ja,
+        _ => Lang::En,
+    }
+}
+
+pub fn class_name(class: u16) -> Option<&'static str> {
+    class_name_in(class, lang())
+}
+
+pub fn class_name_in(class: u16, lang: Lang) -> Option<&'static str> {
+    match lang {
+        Lang::En => CLASS_NAMES
+            .iter()
+            .find(|&&(_, c)| c == class)
+            .map(|&(name, _)| name),
+        Lang::Ja => CLASS_NAMES_JA
+            .iter()
+            .find(|&&(c, _)| c == class)
+            .map(|&(_, name)| name),
+    }
+}
+
+pub fn class_by_name(name: &str) -> Option<u16> {
+    CLASS_NAMES
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|&(_, class)| class)
+}
+
+#[derive(Debug)]
+pub struct PropertyDef {
+    pub class: Option<u16>, // None for the properties of the device object super class
+    pub epc: u8,
+    pub name: &'static str,
+    pub name_ja: &'static str,
+    pub format: Format,
+}
+
+impl PropertyDef {
+    // the name in the language of the process
+    pub fn localized_name(&self) -> &'static str {
+        self.name_in(lang())
+    }
+
+    pub fn name_in(&self, lang: Lang) -> &'static str {
+        match lang {
+            Lang::En => self.name,
+            Lang::Ja => self.name_ja,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Raw,
+    Unsigned {
+        // of the given number of bytes
+        size: usize,
+        // the value is multiplied by 10^exp
+        exp: i32,
+        unit: Option<&'static str>,
+    },
+    Signed {
+        size: usize,
+        exp: i32,
+        unit: Option<&'static str>,
+    },
+    Enum(&'static [(u8, &'static str)]),
+    // cumulative amounts of the smart meter, scaled by its coefficient (0xD3) and unit (0xE1) properties
+    MeterEnergy,
+}
+
+const ON_OFF: &[(u8, &str)] = &[(0x30, "on"), (0x31, "off")];
+
+static DEFS: &[PropertyDef] = &[
+    // device object super class
+    PropertyDef {
+        class: None,
+        epc: 0x80,
+        name: "Operation status",
+        name_ja: "動作状態",
+        format: Format::Enum(ON_OFF),
+    },
+    PropertyDef {
+        class: None,
+        epc: 0x81,
+        name: "Installation location",
+        name_ja: "設置場所",
+        format: Format::Raw,
+    },
+    PropertyDef {
+        class: None,
+        epc: 0x82,
+        name: "Standard version information",
+        name_ja: "規格Version情報",
+        format: Format::Raw,
+    },
+    PropertyDef {
+        class: None,
+        epc: 0x83,
+        name: "Identification number",
+        name_ja: "識別番号",
+        format: Format::Raw,
+    },
+    PropertyDef {
+        class: None,
+        epc: 0x84,
+        name: "Measured instantaneous power consumption",
+        name_ja: "瞬時消費電力計測値",
+        format: Format::Unsigned {
+            size: 2,
+            exp: 0,
+            unit: Some("W"),
+        },
+    },
+    PropertyDef {
+        class: None,
+        epc: 0x85,
+        name: "Measured cumulative electric energy consumption",
+        name_ja: "積算消費電力量計測値",
+        format: Format::Unsigned {
+            size: 4,
+            exp: -3,
+            unit: Some("kWh"),
+        },
+    },
+    PropertyDef {
+        class: None,
+        epc: 0x88,
+        name: "Fault status",
+        name_ja: "異常発生状態",
+        format: Format::Enum(&[(0x41, "fault"), (0x42, "no fault")]),
+    },
+    PropertyDef {
+        class: None,
+        epc: 0x8A,
+        name: "Manufacturer code",
+        name_ja: "メーカコード",
+        format: Format::Raw,
+    },
+    PropertyDef {
+        class: None,
+        epc: 0x8C,
+        name: "Product code",
+        name_ja: "商品コード",
+        format: Format::Raw,
+    },
+    PropertyDef {
+        class: None,
+        epc: 0x9D,
+        name: "Status change announcement property map",
+        name_ja: "状変アナウンスプロパティマップ",
+        format: Format::Raw,
+    },
+    PropertyDef {
+        class: None,
+        epc: 0x9E,
+        name: "Set property map",
+        name_ja: "Setプロパティマップ",
+        format: Format::Raw,
+    },
+    PropertyDef {
+        class: None,
+        epc: 0x9F,
+        name: "Get property map",
+        name_ja: "Getプロパティマップ",
+        format: Format::Raw,
+    },
+    // node profile
+    PropertyDef {
+        class: Some(NODE_PROFILE),
+        epc: 0x80,
+        name: "Operating status",
+        name_ja: "動作状態",
+        format: Format::Enum(&[(0x30, "booting"), (0x31, "not booting")]),
+    },
+    PropertyDef {
+        class: Some(NODE_PROFILE),
+        epc: 0xD3,
+        name: "Number of self-node instances",
+        name_ja: "自ノードインスタンス数",
+        format: Format::Unsigned {
+            size: 3,
+            exp: 0,
+            unit: None,
+        },
+    },
+    PropertyDef {
+        class: Some(NODE_PROFILE),
+        epc: 0xD4,
+        name: "Number of self-node classes",
+        name_ja: "自ノードクラス数",
+        format: Format::Unsigned {
+            size: 2,
+            exp: 0,
+            unit: None,
+        },
+    },
+    PropertyDef {
+        class: Some(NODE_PROFILE),
+        epc: 0xD5,
+        name: "Instance list notification",
+        name_ja: "インスタンスリスト通知",
+        format: Format::Raw,
+    },
+    PropertyDef {
+        class: Some(NODE_PROFILE),
+        epc: 0xD6,
+        name: "Self-node instance list S",
+        name_ja: "自ノードインスタンスリストS",
+        format: Format::Raw,
+    },
+    PropertyDef {
+        class: Some(NODE_PROFILE),
+        epc: 0xD7,
+        name: "Self-node class list S",
+        name_ja: "自ノードクラスリストS",
+        format: Format::Raw,
+    },
+    // temperature sensor
+    PropertyDef {
+        class: Some(TEMPERATURE_SENSOR),
+        epc: 0xE0,
+        name: "Measured temperature value",
+        name_ja: "温度計測値",
+        format: Format::Signed {
+            size: 2,
+            exp: -1,
+            unit: Some("°C"),
+        },
+    },
+    // humidity sensor
+    PropertyDef {
+        class: Some(HUMIDITY_SENSOR),
+        epc: 0xE0,
+        name: "Measured value of relative humidity",
+        name_ja: "相対湿度計測値",
+        format: Format::Unsigned {
+            size: 1,
+            exp: 0,
+            unit: Some("%"),
+        },
+    },
+    // CO2 sensor
+    PropertyDef {
+        class: Some(CO2_SENSOR),
+        epc: 0xE0,
+        name: "Measured value of CO2 concentration",
+        name_ja: "CO2濃度計測値",
+        format: Format::Unsigned {
+            size: 2,
+            exp: 0,
+            unit: Some("ppm"),
+        },
+    },
+    // home air conditioner
+    PropertyDef {
+        class: Some(HOME_AIR_CONDITIONER),
+        epc: 0xA0,
+        name: "Air flow rate setting",
+        name_ja: "風量設定",
+        format: Format::Enum(&[
+            (0x41, "auto"),
+            (0x31, "1"),
+            (0x32, "2"),
+            (0x33, "3"),
+            (0x34, "4"),
+            (0x35, "5"),
+            (0x36, "6"),
+            (0x37, "7"),
+            (0x38, "8"),
+        ]),
+    },
+    PropertyDef {
+        class: Some(HOME_AIR_CONDITIONER),
+        epc: 0xB0,
+        name: "Operation mode setting",
+        name_ja: "運転モード設定",
+        format: Format::Enum(&[
+            (0x40, "other"),
+            (0x41, "automatic"),
+            (0x42, "cooling"),
+            (0x43, "heating"),
+            (0x44, "dehumidification"),
+            (0x45, "air circulator"),
+        ]),
+    },
+    PropertyDef {
+        class: Some(HOME_AIR_CONDITIONER),
+        epc: 0xB3,
+        name: "Set temperature value",
+        name_ja: "温度設定値",
+        format: Format::Unsigned {
+            size: 1,
+            exp: 0,
+            unit: Some("°C"),
+        },
+    },
+    PropertyDef {
+        class: Some(HOME_AIR_CONDITIONER),
+        epc: 0xBA,
+        name: "Measured value of room relative humidity",
+        name_ja: "室内相対湿度計測値",
+        format: Format::Unsigned {
+            size: 1,
+            exp: 0,
+            unit: Some("%"),
+        },
+    },
+    PropertyDef {
+        class: Some(HOME_AIR_CONDITIONER),
+        epc: 0xBB,
+        name: "Measured value of room temperature",
+        name_ja: "室内温度計測値",
+        format: Format::Signed {
+            size: 1,
+            exp: 0,
+            unit: Some("°C"),
+        },
+    },
+    PropertyDef {
+        class: Some(HOME_AIR_CONDITIONER),
+        epc: 0xBE,
+        name: "Measured outdoor air temperature",
+        name_ja: "外気温度計測値",
+        format: Format::Signed {
+            size: 1,
+            exp: 0,
+            unit: Some("°C"),
+        },
+    },
+    // household solar power generation
+    PropertyDef {
+        class: Some(PV_POWER_GENERATION),
+        epc: 0xA0,
+        name: "Output power control setting 1",
+        name_ja: "出力制御設定1",
+        format: Format::Unsigned {
+            size: 1,
+            exp: 0,
+            unit: Some("%"),
+        },
+    },
+    PropertyDef {
+        class: Some(PV_POWER_GENERATION),
+        epc: 0xA1,
+        name: "Output power control setting 2",
+        name_ja: "出力制御設定2",
+        format: Format::Unsigned {
+            size: 2,
+            exp: 0,
+            unit: Some("W"),
+        },
+    },
+    PropertyDef {
+        class: Some(PV_POWER_GENERATION),
+        epc: 0xD0,
+        name: "System-interconnected type",
+        name_ja: "系統連系状態",
+        format: Format::Enum(&[
+            (
+                0x00,
+                "system interconnected (reverse power flow acceptable)",
+            ),
+            (0x01, "independent"),
+            (
+                0x02,
+                "system interconnected (reverse power flow not acceptable)",
+            ),
+        ]),
+    },
+    PropertyDef {
+        class: Some(PV_POWER_GENERATION),
+        epc: 0xD1,
+        name: "Output power restraint status",
+        name_ja: "出力抑制状態",
+        format: Format::Enum(&[
+            (0x41, "ongoing restraint (output power control)"),
+            (0x42, "ongoing restraint (except output power control)"),
+            (
+                0x43,
+                "ongoing restraint (output power control and except output power control)",
+            ),
+            (0x44, "not restraining"),
+        ]),
+    },
+    PropertyDef {
+        class: Some(PV_POWER_GENERATION),
+        epc: 0xE0,
+        name: "Measured instantaneous amount of electricity generated",
+        name_ja: "瞬時発電電力計測値",
+        format: Format::Unsigned {
+            size: 2,
+            exp: 0,
+            unit: Some("W"),
+        },
+    },
+    PropertyDef {
+        class: Some(PV_POWER_GENERATION),
+        epc: 0xE1,
+        name: "Measured cumulative amount of electricity generated",
+        name_ja: "積算発電電力量計測値",
+        format: Format::Unsigned {
+            size: 4,
+            exp: -3,
+            unit: Some("kWh"),
+        },
+    },
+    PropertyDef {
+        class: Some(PV_POWER_GENERATION),
+        epc: 0xE3,
+        name: "Measured cumulative amount of electricity sold",
+        name_ja: "積算売電電力量計測値",
+        format: Format::Unsigned {
+            size: 4,
+            exp: -3,
+            unit: Some("kWh"),
+        },
+    },
+    PropertyDef {
+        class: Some(PV_POWER_GENERATION),
+        epc: 0xE8,
+        name: "Rated power generation output (system-interconnected)",
+        name_ja: "定格発電電力値（系統連系時）",
+        format: Format::Unsigned {
+            size: 2,
+            exp: 0,
+            unit: Some("W"),
+        },
+    },
+    // storage battery
+    PropertyDef {
+        class: Some(STORAGE_BATTERY),
+        epc: 0xD3,
+        name: "Measured instantaneous charging/discharging electric power",
+        name_ja: "瞬時充放電電力計測値",
+        format: Format::Signed {
+            size: 4,
+            exp: 0,
+            unit: Some("W"),
+        },
+    },
+    PropertyDef {
+        class: Some(STORAGE_BATTERY),
+        epc: 0xDA,
+        name: "Operation mode setting",
+        name_ja: "運転モード設定",
+        format: Format::Enum(&[
+            (0x40, "other"),
+            (0x41, "rapid charging"),
+            (0x42, "charging"),
+            (0x43, "discharging"),
+            (0x44, "standby"),
+            (0x45, "test"),
+            (0x46, "automatic"),
+            (0x48, "restart"),
+            (0x49, "effective capacity recalculation processing"),
+        ]),
+    },
+    PropertyDef {
+        class: Some(STORAGE_BATTERY),
+        epc: 0xE2,
+        name: "Remaining stored electricity 1",
+        name_ja: "蓄電残量1",
+        format: Format::Unsigned {
+            size: 4,
+            exp: 0,
+            unit: Some("Wh"),
+        },
+    },
+    PropertyDef {
+        class: Some(STORAGE_BATTERY),
+        epc: 0xE4,
+        name: "Remaining stored electricity 3",
+        name_ja: "蓄電残量3",
+        format: Format::Unsigned {
+            size: 1,
+            exp: 0,
+            unit: Some("%"),
+        },
+    },
+    // electric vehicle charger/discharger
+    PropertyDef {
+        class: Some(EV_CHARGER_DISCHARGER),
+        epc: 0xD3,
+        name: "Measured instantaneous charging/discharging electric power",
+        name_ja: "瞬時充放電電力計測値",
+        format: Format::Signed {
+            size: 4,
+            exp: 0,
+            unit: Some("W"),
+        },
+    },
+    // low-voltage smart electric energy meter
+    PropertyDef {
+        class: Some(SMART_METER),
+        epc: 0xD3,
+        name: "Coefficient",
+        name_ja: "係数",
+        format: Format::Unsigned {
+            size: 4,
+            exp: 0,
+            unit: None,
+        },
+    },
+    PropertyDef {
+        class: Some(SMART_METER),
+        epc: 0xD7,
+        name: "Number of effective digits for cumulative amounts of electric energy",
+        name_ja: "積算電力量有効桁数",
+        format: Format::Unsigned {
+            size: 1,
+            exp: 0,
+            unit: None,
+        },
+    },
+    PropertyDef {
+        class: Some(SMART_METER),
+        epc: 0xE0,
+        name: "Measured cumulative amount of electric energy (normal direction)",
+        name_ja: "積算電力量計測値（正方向計測値）",
+        format: Format::MeterEnergy,
+    },
+    PropertyDef {
+        class: Some(SMART_METER),
+        epc: 0xE1,
+        name: "Unit for cumulative amounts of electric energy",
+        name_ja: "積算電力量単位（正方向、逆方向計測値）",
+        format: Format::Enum(&[
+            (0x00, "1 kWh"),
+            (0x01, "0.1 kWh"),
+            (0x02, "0.01 kWh"),
+            (0x03, "0.001 kWh"),
+            (0x04, "0.0001 kWh"),
+            (0x0A, "10 kWh"),
+            (0x0B, "100 kWh"),
+            (0x0C, "1000 kWh"),
+            (0x0D, "10000 kWh"),
+        ]),
+    },
+    PropertyDef {
+        class: Some(SMART_METER),
+        epc: 0xE3,
+        name: "Measured cumulative amount of electric energy (reverse direction)",
+        name_ja: "積算電力量計測値（逆方向計測値）",
+        format: Format::MeterEnergy,
+    },
+    PropertyDef {
+        class: Some(SMART_METER),
+        epc: 0xE2,
+        name:
+            "Historical data of measured cumulative amounts of electric energy 1 (normal direction)",
+        name_ja: "積算電力量計測値履歴1（正方向計測値）",
+        format: Format::Raw,
+    },
+    PropertyDef {
+        class: Some(SMART_METER),
+        epc: 0xE5,
+        name:
+            "Day for which the historical data of measured cumulative amounts of electric energy \
+               is to be retrieved 1",
+        name_ja: "積算履歴収集日1",
+        format: Format::Unsigned {
+            size: 1,
+            exp: 0,
+            unit: None,
+        },
+    },
+    PropertyDef {
+        class: Some(SMART_METER),
+        epc: 0xE7,
+        name: "Measured instantaneous electric power",
+        name_ja: "瞬時電力計測値",
+        format: Format::Signed {
+            size: 4,
+            exp: 0,
+            unit: Some("W"),
+        },
+    },
+];
+
+pub fn lookup(class: u16, epc: ElU8) -> Option<&'static PropertyDef> {
+    DEFS.iter()
+        .find(|d| d.class == Some(class) && d.epc == epc.0)
+        .or_else(|| DEFS.iter().find(|d| d.class.is_none() && d.epc == epc.0))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Number(f64),
+    Text(&'static str),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Text(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PropertyValue {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<&'static str>,
+    // the factor applied to the raw integer to get the value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    pub raw: EDT,
+}
+
+impl fmt::Display for PropertyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.name.unwrap_or("Unknown property"))?;
+        match &self.value {
+            Some(v) => write!(f, "{}", v)?,
+            None => write!(f, "0x{}", self.raw.to_hex())?,
+        }
+        if let Some(unit) = self.unit {
+            write!(f, " {}", unit)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn decode(class: u16, epc: ElU8, edt: &EDT, props: &Props) -> PropertyValue {
+    let mut pv = PropertyValue {
+        name: None,
+        value: None,
+        unit: None,
+        scale: None,
+        raw: edt.clone(),
+    };
+    let Some(def) = lookup(class, epc) else {
+        return pv;
+    };
+    pv.name = Some(def.localized_name());
+    match def.format {
+        Format::Raw => {}
+        Format::Unsigned { exp, unit, .. } => {
+            if let Some(n) = unsigned(edt).filter(|&n| !is_unsigned_out_of_range(n, edt.0.len())) {
+                pv.value = Some(Value::Number(scaled(n as f64, exp)));
+                pv.unit = unit;
+                pv.scale = Some(scaled(1.0, exp));
+            }
+        }
+        Format::Signed { exp, unit, .. } => {
+            if let Some(n) = signed(edt).filter(|&n| !is_signed_out_of_range(n, edt.0.len())) {
+                pv.value = Some(Value::Number(scaled(n as f64, exp)));
+                pv.unit = unit;
+                pv.scale = Some(scaled(1.0, exp));
+            }
+        }
+        Format::Enum(variants) => {
+            if let [b] = edt.0[..] {
+                pv.value = variants
+                    .iter()
+                    .find(|(code, _)| *code == b.0)
+                    .map(|(_, s)| Value::Text(s));
+            }
+        }
+        // the amounts are 4 bytes long, the others being malformed
+        Format::MeterEnergy if edt.0.len() == 4 => {
+            if let Some((value, unit, scale)) = unsigned(edt).and_then(|n| meter_energy(n, props)) {
+                pv.value = Some(Value::Number(value));
+                pv.unit = unit;
+                pv.scale = Some(scale);
+            }
+        }
+        Format::MeterEnergy => {}
+    }
+    pv
+}
+
+// the EDT of a value given as text, such as on, 25C or 60%, by the format of the property, which is
+// checked to fit it before anything is sent
+pub fn encode(class: u16, epc: ElU8, value: &str) -> anyhow::Result<EDT> {
+    let Some(def) = lookup(class, epc) else {
+        anyhow::bail!(
+            "unknown property {:?}, whose EDT has to be given in hex",
+            epc
+        );
+    };
+    match def.format {
+        Format::Raw | Format::MeterEnergy => anyhow::bail!(
+            "the format of {} is unknown, its EDT has to be given in hex",
+            def.name
+        ),
+        Format::Enum(variants) => match variants
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(value))
+        {
+            Some((code, _)) => Ok(EDT::from(vec![*code])),
+            None => {
+                let names: Vec<_> = variants.iter().map(|(_, name)| *name).collect();
+                anyhow::bail!("expected one of {}", names.join(", "))
+            }
+        },
+        Format::Unsigned { size, exp, unit } => {
+            let n = integer(value, exp, unit)?;
+            // the overflow and underflow codes are never written
+            let max = (u64::MAX >> (64 - 8 * size as u32)) - 2;
+            if n < 0 || n as u64 > max {
+                anyhow::bail!("out of range 0 to {}", scaled(max as f64, exp));
+            }
+            Ok(EDT::from((n as u64).to_be_bytes()[8 - size..].to_vec()))
+        }
+        Format::Signed { size, exp, unit } => {
+            let n = integer(value, exp, unit)?;
+            // nor are the largest and the smallest integers
+            let lim = i64::MAX >> (64 - 8 * size as u32);
+            if n < -lim || n >= lim {
+                anyhow::bail!(
+                    "out of range {} to {}",
+                    scaled(-lim as f64, exp),
+                    scaled((lim - 1) as f64, exp)
+                );
+            }
+            Ok(EDT::from(n.to_be_bytes()[8 - size..].to_vec()))
+        }
+    }
+}
+
+// the raw integer of a number followed by the unit if any, where the degree sign can be left out
+fn integer(value: &str, exp: i32, unit: Option<&str>) -> anyhow::Result<i64> {
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(end);
+    let suffix = suffix.trim();
+    if !suffix.is_empty() {
+        let matches = unit.is_some_and(|u| {
+            u.eq_ignore_ascii_case(suffix) || u.trim_start_matches('°').eq_ignore_ascii_case(suffix)
+        });
+        if !matches {
+            anyhow::bail!("expected a number in {}", unit.unwrap_or("no unit"));
+        }
+    }
+    let x: f64 = number.parse()?;
+    let n = scaled(x, -exp);
+    if (n - n.round()).abs() > 1e-9 {
+        anyhow::bail!("not a multiple of {}", scaled(1.0, exp));
+    }
+    Ok(n.round() as i64)
+}
+
+// whether the size of the EDT could be of the property, which an unknown property always passes
+pub fn is_plausible_size(class: u16, epc: ElU8, len: usize) -> bool {
+    match lookup(class, epc).map(|d| d.format) {
+        None | Some(Format::Raw) => true,
+        Some(Format::Unsigned { size, .. } | Format::Signed { size, .. }) => len == size,
+        Some(Format::Enum(_)) => len == 1,
+        Some(Format::MeterEnergy) => len == 4,
+    }
+}
+
+// scales a raw cumulative amount of the smart meter, returning the value, its unit, and the applied
+// factor, or none if the coefficient of the meter overflows it
+pub fn meter_energy(n: u64, props: &Props) -> Option<(f64, Option<&'static str>, f64)> {
+    // the coefficient is regarded as 1 when the meter doesn't implement it
+    let coefficient = props
+        .get(&smart_meter::COEFFICIENT)
+        .and_then(unsigned)
+        .unwrap_or(1);
+    let exp = props
+        .get(&smart_meter::ENERGY_UNIT)
+        .and_then(|edt| edt.0.first())
+        .and_then(|b| meter_unit_exp(b.0));
+    let n = n.checked_mul(coefficient)? as f64;
+    Some(match exp {
+        Some(exp) => (scaled(n, exp), Some("kWh"), scaled(coefficient as f64, exp)),
+        // without the unit property, only the coefficient can be applied
+        None => (n, None, coefficient as f64),
+    })
+}
+
+fn unsigned(edt: &EDT) -> Option<u64> {
+    if edt.0.is_empty() || edt.0.len() > 8 {
+        return None;
+    }
+    Some(edt.0.iter().fold(0, |acc, b| (acc << 8) | b.0 as u64))
+}
+
+fn signed(edt: &EDT) -> Option<i64> {
+    let n = unsigned(edt)?;
+    let bits = 64 - 8 * edt.0.len() as u32;
+    Some(((n << bits) as i64) >> bits)
+}
+
+// measured values use the largest two unsigned integers of their size as overflow and underflow codes
+fn is_unsigned_out_of_range(n: u64, len: usize) -> bool {
+    let max = u64::MAX >> (64 - 8 * len as u32);
+    n == max || n == max - 1
+}
+
+// and the largest and the smallest signed integers
+fn is_signed_out_of_range(n: i64, len: usize) -> bool {
+    let max = i64::MAX >> (64 - 8 * len as u32);
+    n == max || n == -max - 1
+}
+
+// dividing by a power of 10 rather than multiplying by its reciprocal keeps e.g. 1234 * 10^-3 as 1.234
+fn scaled(n: f64, exp: i32) -> f64 {
+    if exp < 0 {
+        n / 10f64.powi(-exp)
+    } else {
+        n * 10f64.powi(exp)
+    }
+}
+
+fn meter_unit_exp(code: u8) -> Option<i32> {
+    match code {
+        0x00..=0x04 => Some(-(code as i32)),
+        0x0A..=0x0D => Some((code - 0x09) as i32),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let aircon = HOME_AIR_CONDITIONER;
+        let hex = |class, epc, value| encode(class, ElU8(epc), value).map(|edt| edt.to_hex());
+        assert_eq!(hex(aircon, 0x80, "on").unwrap(), "30");
+        assert_eq!(hex(aircon, 0xB0, "Cooling").unwrap(), "42");
+        assert_eq!(hex(aircon, 0xB3, "25C").unwrap(), "19");
+        assert_eq!(hex(aircon, 0xB3, "25°C").unwrap(), "19");
+        assert_eq!(hex(aircon, 0xB3, "25").unwrap(), "19");
+        assert_eq!(hex(PV_POWER_GENERATION, 0xA0, "60%").unwrap(), "3C");
+        assert_eq!(hex(PV_POWER_GENERATION, 0xA1, "3000W").unwrap(), "0BB8");
+        assert_eq!(hex(TEMPERATURE_SENSOR, 0xE0, "-2.5").unwrap(), "FFE7");
+        // checked before being sent
+        assert_eq!(
+            hex(aircon, 0xB3, "25.5C").unwrap_err().to_string(),
+            "not a multiple of 1"
+        );
+        assert_eq!(
+            hex(aircon, 0xB3, "300").unwrap_err().to_string(),
+            "out of range 0 to 253"
+        );
+        assert!(hex(aircon, 0xB3, "25%").is_err());
+        assert!(hex(aircon, 0xB3, "-1").is_err());
+        // only 0x7F and 0x80 are the overflow and underflow codes of a signed byte
+        assert_eq!(hex(aircon, 0xBE, "-127").unwrap(), "81");
+        assert_eq!(hex(aircon, 0xBE, "126").unwrap(), "7E");
+        assert_eq!(
+            hex(aircon, 0xBE, "127").unwrap_err().to_string(),
+            "out of range -127 to 126"
+        );
+        assert!(hex(aircon, 0xBE, "-128").is_err());
+        assert_eq!(
+            hex(aircon, 0x80, "maybe").unwrap_err().to_string(),
+            "expected one of on, off"
+        );
+        assert!(hex(aircon, 0x81, "living").is_err());
+        assert!(hex(aircon, 0xF0, "1").is_err());
+    }
+
+    #[test]
+    fn test_names_in() {
+        let def = lookup(HOME_AIR_CONDITIONER, ElU8(0xBB)).unwrap();
+        assert_eq!(def.name_in(Lang::En), "Measured value of room temperature");
+        assert_eq!(def.name_in(Lang::Ja), "室内温度計測値");
+        assert_eq!(class_name_in(SMART_METER, Lang::En), Some("smart-meter"));
+        assert_eq!(
+            class_name_in(SMART_METER, Lang::Ja),
+            Some("低圧スマート電力量メータ")
+        );
+        assert!(DEFS.iter().all(|d| !d.name_ja.is_empty()));
+        assert!(CLASS_NAMES
+            .iter()
+            .all(|&(_, class)| class_name_in(class, Lang::Ja).is_some()));
+        assert_eq!("ja".parse::<Lang>().unwrap(), Lang::Ja);
+        assert!("fr".parse::<Lang>().is_err());
+    }
+
+    #[test]
+    fn test_decode() {
+        let props = Props::new();
+        assert_eq!(
+            decode(0x0130, ElU8(0x80), &EDT::from(vec![0x30]), &props),
+            PropertyValue {
+                name: Some("Operation status"),
+                value: Some(Value::Text("on")),
+                unit: None,
+                scale: None,
+                raw: EDT::from(vec![0x30]),
+            }
+        );
+        assert_eq!(
+            decode(0x0EF0, ElU8(0x80), &EDT::from(vec![0x30]), &props).value,
+            Some(Value::Text("booting"))
+        );
+        assert_eq!(
+            decode(0x0130, ElU8(0xBE), &EDT::from(vec![0xFE]), &props),
+            PropertyValue {
+                name: Some("Measured outdoor air temperature"),
+                value: Some(Value::Number(-2.0)),
+                unit: Some("°C"),
+                scale: Some(1.0),
+                raw: EDT::from(vec![0xFE]),
+            }
+        );
+        assert_eq!(
+            decode(
+                0x0288,
+                ElU8(0xE7),
+                &EDT::from(vec![0xFF, 0xFF, 0xFE, 0x0C]),
+                &props
+            )
+            .value,
+            Some(Value::Number(-500.0))
+        );
+        assert_eq!(
+            decode(
+                0x0130,
+                ElU8(0x85),
+                &EDT::from(vec![0x00, 0x00, 0x04, 0xD2]),
+                &props
+            )
+            .value,
+            Some(Value::Number(1.234))
+        );
+        assert_eq!(
+            decode(0x0130, ElU8(0xF0), &EDT::from(vec![0x01]), &props),
+            PropertyValue {
+                name: None,
+                value: None,
+                unit: None,
+                scale: None,
+                raw: EDT::from(vec![0x01]),
+            }
+        );
+    }
+
+    #[test]
+    fn test_decode_sensors() {
+        let props = Props::new();
+        assert_eq!(
+            decode(0x0011, ElU8(0xE0), &EDT::from(vec![0x00, 0xEB]), &props),
+            PropertyValue {
+                name: Some("Measured temperature value"),
+                value: Some(Value::Number(23.5)),
+                unit: Some("°C"),
+                scale: Some(0.1),
+                raw: EDT::from(vec![0x00, 0xEB]),
+            }
+        );
+        assert_eq!(
+            decode(0x0011, ElU8(0xE0), &EDT::from(vec![0xFF, 0x9C]), &props).value,
+            Some(Value::Number(-10.0))
+        );
+        assert_eq!(
+            decode(0x0012, ElU8(0xE0), &EDT::from(vec![0x37]), &props).value,
+            Some(Value::Number(55.0))
+        );
+        assert_eq!(
+            decode(0x001B, ElU8(0xE0), &EDT::from(vec![0x01, 0xa4]), &props).value,
+            Some(Value::Number(420.0))
+        );
+
+        // overflow and underflow
+        for edt in [vec![0x7F, 0xFF], vec![0x80, 0x00]] {
+            let pv = decode(0x0011, ElU8(0xE0), &EDT::from(edt), &props);
+            assert_eq!(pv.value, None);
+            assert_eq!(pv.unit, None);
+        }
+        for edt in [vec![0xFF], vec![0xFE]] {
+            assert_eq!(
+                decode(0x0012, ElU8(0xE0), &EDT::from(edt), &props).value,
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_pv_power_generation() {
+        let props = Props::new();
+        assert_eq!(
+            decode(0x0279, ElU8(0xE0), &EDT::from(vec![0x0B, 0xB8]), &props).value,
+            Some(Value::Number(3000.0))
+        );
+        assert_eq!(
+            decode(
+                0x0279,
+                ElU8(0xE1),
+                &EDT::from(vec![0x00, 0x01, 0xE2, 0x40]),
+                &props
+            ),
+            PropertyValue {
+                name: Some("Measured cumulative amount of electricity generated"),
+                value: Some(Value::Number(123.456)),
+                unit: Some("kWh"),
+                scale: Some(0.001),
+                raw: EDT::from(vec![0x00, 0x01, 0xE2, 0x40]),
+            }
+        );
+        assert_eq!(
+            decode(0x0279, ElU8(0xD0), &EDT::from(vec![0x02]), &props).value,
+            Some(Value::Text(
+                "system interconnected (reverse power flow not acceptable)"
+            ))
+        );
+        assert_eq!(
+            decode(0x0279, ElU8(0xD1), &EDT::from(vec![0x44]), &props).value,
+            Some(Value::Text("not restraining"))
+        );
+    }
+
+    #[test]
+    fn test_decode_meter_energy() {
+        let edt = EDT::from(vec![0x00, 0x00, 0x04, 0xD2]);
+        let mut props = Props::new();
+        assert_eq!(
+            decode(0x0288, ElU8(0xE0), &edt, &props),
+            PropertyValue {
+                name: Some("Measured cumulative amount of electric energy (normal direction)"),
+                value: Some(Value::Number(1234.0)),
+                unit: None,
+                scale: Some(1.0),
+                raw: edt.clone(),
+            }
+        );
+
+        props.insert(ElU8(0xE1), EDT::from(vec![0x01]));
+        assert_eq!(
+            decode(0x0288, ElU8(0xE0), &edt, &props),
+            PropertyValue {
+                name: Some("Measured cumulative amount of electric energy (normal direction)"),
+                value: Some(Value::Number(123.4)),
+                unit: Some("kWh"),
+                scale: Some(0.1),
+                raw: edt.clone(),
+            }
+        );
+
+        props.insert(ElU8(0xD3), EDT::from(vec![0x00, 0x00, 0x00, 0x0A]));
+        props.insert(ElU8(0xE1), EDT::from(vec![0x03]));
+        assert_eq!(
+            decode(0x0288, ElU8(0xE3), &edt, &props),
+            PropertyValue {
+                name: Some("Measured cumulative amount of electric energy (reverse direction)"),
+                value: Some(Value::Number(12.34)),
+                unit: Some("kWh"),
+                scale: Some(0.01),
+                raw: edt.clone(),
+            }
+        );
+
+        // a malformed coefficient overflowing the amount, and an amount of the wrong length
+        props.insert(ElU8(0xD3), EDT::from(vec![0xFF; 8]));
+        assert_eq!(decode(0x0288, ElU8(0xE0), &edt, &props).value, None);
+        props.remove(&ElU8(0xD3));
+        let long = EDT::from(vec![0x00, 0x00, 0x00, 0x04, 0xD2]);
+        assert_eq!(decode(0x0288, ElU8(0xE0), &long, &props).value, None);
+    }
+}
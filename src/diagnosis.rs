@@ -0,0 +1,142 @@
+use serde::Serialize;
+use std::{fmt, net::IpAddr};
+
+// what a host which didn't answer a request is found to do, telling the devices which are off from
+// the networks which only let multicast through
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    // answers neither unicast nor multicast, as if off or unplugged
+    Silent,
+    // answers multicast discovery but no unicast request, which may be filtered
+    UnicastBlocked,
+    // the node answers unicast requests, only the object doesn't, being busy or missing on the node
+    ObjectSilent,
+    // answered unicast requests before but nothing any more, as if off or rebooting
+    Lost,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Diagnosis {
+    pub reason: Reason,
+    // whether the host is in the neighbor table, unknown for IPv6 and outside Linux
+    pub arp: Option<bool>,
+    // seconds since the host last answered a unicast request
+    pub last_answer: Option<u64>,
+}
+
+impl Diagnosis {
+    pub fn new(
+        answers_multicast: bool,
+        answers_unicast: bool,
+        last_answer: Option<u64>,
+        arp: Option<bool>,
+    ) -> Self {
+        let reason = match (answers_multicast, answers_unicast, last_answer) {
+            (true, true, _) => Reason::ObjectSilent,
+            (true, false, _) => Reason::UnicastBlocked,
+            (false, _, None) => Reason::Silent,
+            (false, _, Some(_)) => Reason::Lost,
+        };
+        Self {
+            reason,
+            arp,
+            last_answer,
+        }
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.reason {
+            Reason::Silent => write!(f, "the host answers neither unicast nor multicast")?,
+            Reason::UnicastBlocked => write!(
+                f,
+                "the host answers multicast discovery but no unicast request, which may be filtered"
+            )?,
+            Reason::ObjectSilent => write!(f, "the node answers but the object doesn't")?,
+            Reason::Lost => write!(f, "the host stopped answering")?,
+        }
+        if let Some(secs) = self.last_answer {
+            write!(f, ", last answered {}s ago", secs)?;
+        }
+        match self.arp {
+            Some(true) => write!(f, ", in the ARP table"),
+            Some(false) => write!(f, ", not in the ARP table"),
+            None => Ok(()),
+        }
+    }
+}
+
+// a request no response came to, which the callers reporting it diagnose
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timeout {
+    pub addr: IpAddr,
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request timed out")
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+// looks the host up in the neighbor table of the kernel, which holds the hosts on the link whose MAC
+// addresses were resolved recently
+pub fn arp(addr: IpAddr) -> Option<bool> {
+    let IpAddr::V4(ip) = addr else {
+        return None;
+    };
+    let table = std::fs::read_to_string("/proc/net/arp").ok()?;
+    Some(is_resolved(&table, &ip.to_string()))
+}
+
+// entries which were never resolved are flagged 0x0
+fn is_resolved(table: &str, ip: &str) -> bool {
+    table.lines().skip(1).any(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        matches!(fields[..], [addr, _, flags, ..] if addr == ip && flags != "0x0")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_resolved() {
+        let table = "IP address       HW type     Flags       HW address            Mask     Device\n\
+                     192.168.1.10     0x1         0x2         00:11:22:33:44:55     *        eth0\n\
+                     192.168.1.11     0x1         0x0         00:00:00:00:00:00     *        eth0\n";
+        assert!(is_resolved(table, "192.168.1.10"));
+        assert!(!is_resolved(table, "192.168.1.11"));
+        assert!(!is_resolved(table, "192.168.1.1"));
+    }
+
+    #[test]
+    fn test_diagnosis() {
+        let diagnosis = Diagnosis::new(true, false, None, Some(true));
+        assert_eq!(diagnosis.reason, Reason::UnicastBlocked);
+        assert_eq!(
+            diagnosis.to_string(),
+            "the host answers multicast discovery but no unicast request, which may be filtered, \
+             in the ARP table"
+        );
+        let diagnosis = Diagnosis::new(false, false, Some(42), None);
+        assert_eq!(diagnosis.reason, Reason::Lost);
+        assert_eq!(
+            diagnosis.to_string(),
+            "the host stopped answering, last answered 42s ago"
+        );
+        assert_eq!(
+            Diagnosis::new(false, false, None, Some(false)).reason,
+            Reason::Silent
+        );
+        // the node answering unicast only leaves the object
+        assert_eq!(
+            Diagnosis::new(true, true, None, None).reason,
+            Reason::ObjectSilent
+        );
+    }
+}
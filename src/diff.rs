@@ -0,0 +1,285 @@
+use crate::{
+    consts::epc,
+    packet::{ElU8, EOJ},
+};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    io::BufRead,
+    net::IpAddr,
+};
+
+// the fields of the events written by `--output json` which describe the objects
+#[derive(Debug, Deserialize)]
+struct Record {
+    event: String,
+    addr: Option<IpAddr>,
+    eoj: Option<EOJ>,
+    instances: Option<Vec<EOJ>>,
+    svi: Option<String>,
+    anno_props: Option<Vec<ElU8>>,
+    get_props: Option<Vec<ElU8>>,
+    set_props: Option<Vec<ElU8>>,
+    epc: Option<ElU8>,
+    raw: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Maps {
+    pub anno: Vec<ElU8>,
+    pub get: Vec<ElU8>,
+    pub set: Vec<ElU8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Object {
+    pub addr: IpAddr,
+    pub eoj: EOJ,
+    // identification number, which tells the object apart wherever it moves
+    pub id: Option<String>,
+    // standard version information, the release of the specification the firmware implements
+    pub svi: Option<String>,
+    pub maps: Option<Maps>,
+}
+
+// objects are matched by their identification numbers if they have them, by their addresses otherwise
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Key {
+    Id(EOJ, String),
+    Addr(IpAddr, EOJ),
+}
+
+impl Object {
+    fn key(&self) -> Key {
+        match &self.id {
+            Some(id) => Key::Id(self.eoj, id.clone()),
+            None => Key::Addr(self.addr, self.eoj),
+        }
+    }
+}
+
+// reads the objects out of a scan saved with `--output json`, the latest of the repeated events winning
+pub fn load(reader: impl BufRead) -> anyhow::Result<Vec<Object>> {
+    let mut objects = BTreeMap::<(IpAddr, EOJ), Object>::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record =
+            serde_json::from_str(&line).with_context(|| format!("line {}", i + 1))?;
+        let (Some(addr), Some(eoj)) = (record.addr, record.eoj) else {
+            continue;
+        };
+        let object = |eoj| Object {
+            addr,
+            eoj,
+            id: None,
+            svi: None,
+            maps: None,
+        };
+        match record.event.as_str() {
+            // the node profiles are left out, as the scan doesn't read their identification numbers
+            "discovery" => {
+                for instance in record.instances.unwrap_or_default() {
+                    objects
+                        .entry((addr, instance))
+                        .or_insert_with(|| object(instance));
+                }
+            }
+            "sync" => {
+                let o = objects.entry((addr, eoj)).or_insert_with(|| object(eoj));
+                o.svi = record.svi;
+                o.maps = Some(Maps {
+                    anno: record.anno_props.unwrap_or_default(),
+                    get: record.get_props.unwrap_or_default(),
+                    set: record.set_props.unwrap_or_default(),
+                });
+            }
+            "property" if record.epc == Some(epc::IDENTIFICATION_NUMBER) => {
+                objects.entry((addr, eoj)).or_insert_with(|| object(eoj)).id = record.raw;
+            }
+            _ => {}
+        }
+    }
+    Ok(objects.into_values().collect())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    Added(Object),
+    Removed(Object),
+    Moved {
+        eoj: EOJ,
+        from: IpAddr,
+        to: IpAddr,
+    },
+    Firmware {
+        addr: IpAddr,
+        eoj: EOJ,
+        from: String,
+        to: String,
+    },
+    Map {
+        addr: IpAddr,
+        eoj: EOJ,
+        map: &'static str,
+        added: Vec<ElU8>,
+        removed: Vec<ElU8>,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added(o) => write!(f, "+ [{}] {:?} added", o.addr, o.eoj),
+            Self::Removed(o) => write!(f, "- [{}] {:?} removed", o.addr, o.eoj),
+            Self::Moved { eoj, from, to } => write!(f, "~ {:?} moved from {} to {}", eoj, from, to),
+            Self::Firmware {
+                addr,
+                eoj,
+                from,
+                to,
+            } => write!(
+                f,
+                "~ [{}] {:?} standard version information: {} -> {}",
+                addr, eoj, from, to
+            ),
+            Self::Map {
+                addr,
+                eoj,
+                map,
+                added,
+                removed,
+            } => {
+                write!(f, "~ [{}] {:?} {} property map:", addr, eoj, map)?;
+                for epc in added {
+                    write!(f, " +{:?}", epc)?;
+                }
+                for epc in removed {
+                    write!(f, " -{:?}", epc)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn diff_map(old: &[ElU8], new: &[ElU8]) -> (Vec<ElU8>, Vec<ElU8>) {
+    let (old, new) = (
+        old.iter().collect::<BTreeSet<_>>(),
+        new.iter().collect::<BTreeSet<_>>(),
+    );
+    (
+        new.difference(&old).map(|&&e| e).collect(),
+        old.difference(&new).map(|&&e| e).collect(),
+    )
+}
+
+// what is left unknown in either scan, such as the maps of the objects which couldn't be synced,
+// isn't taken for a change
+pub fn diff(old: &[Object], new: &[Object]) -> Vec<Change> {
+    let old = old.iter().map(|o| (o.key(), o)).collect::<BTreeMap<_, _>>();
+    let new = new.iter().map(|o| (o.key(), o)).collect::<BTreeMap<_, _>>();
+    let mut changes = vec![];
+    for (key, o) in &old {
+        if !new.contains_key(key) {
+            changes.push(Change::Removed((*o).clone()));
+        }
+    }
+    for (key, n) in &new {
+        let Some(o) = old.get(key) else {
+            changes.push(Change::Added((*n).clone()));
+            continue;
+        };
+        if o.addr != n.addr {
+            changes.push(Change::Moved {
+                eoj: n.eoj,
+                from: o.addr,
+                to: n.addr,
+            });
+        }
+        if let (Some(from), Some(to)) = (&o.svi, &n.svi) {
+            if from != to {
+                changes.push(Change::Firmware {
+                    addr: n.addr,
+                    eoj: n.eoj,
+                    from: from.clone(),
+                    to: to.clone(),
+                });
+            }
+        }
+        let (Some(om), Some(nm)) = (&o.maps, &n.maps) else {
+            continue;
+        };
+        for (map, old, new) in [
+            ("announcement", &om.anno, &nm.anno),
+            ("set", &om.set, &nm.set),
+            ("get", &om.get, &nm.get),
+        ] {
+            let (added, removed) = diff_map(old, new);
+            if !added.is_empty() || !removed.is_empty() {
+                changes.push(Change::Map {
+                    addr: n.addr,
+                    eoj: n.eoj,
+                    map,
+                    added,
+                    removed,
+                });
+            }
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = r#"{"event":"discovery","addr":"192.168.1.10","eoj":"0EF0:01","instances":["0130:01","0288:01"]}
+{"event":"sync","addr":"192.168.1.10","eoj":"0130:01","svi":"00004A00","anno_props":["80"],"get_props":["80","83","B0"],"set_props":["80","B0"]}
+{"event":"property","addr":"192.168.1.10","eoj":"0130:01","epc":"83","name":"Identification number","raw":"FE000001"}
+{"event":"sync","addr":"192.168.1.10","eoj":"0288:01","svi":"00004A00","anno_props":["80"],"get_props":["80","E7"],"set_props":[]}
+"#;
+
+    const NEW: &str = r#"{"event":"discovery","addr":"192.168.1.20","eoj":"0EF0:01","instances":["0130:01"]}
+{"event":"sync","addr":"192.168.1.20","eoj":"0130:01","svi":"00005000","anno_props":["80"],"get_props":["80","83","B0","BB"],"set_props":["80","B0"]}
+{"event":"property","addr":"192.168.1.20","eoj":"0130:01","epc":"83","name":"Identification number","raw":"FE000001"}
+{"event":"discovery","addr":"192.168.1.11","eoj":"0EF0:01","instances":["0279:01"]}
+"#;
+
+    #[test]
+    fn test_load() {
+        let objects = load(OLD.as_bytes()).unwrap();
+        let eojs = objects.iter().map(|o| o.eoj).collect::<Vec<_>>();
+        assert_eq!(eojs, vec![EOJ::new(0x0130, 1), EOJ::new(0x0288, 1)]);
+        assert_eq!(objects[0].id.as_deref(), Some("FE000001"));
+        assert_eq!(objects[0].svi.as_deref(), Some("00004A00"));
+        assert_eq!(objects[1].id, None);
+        assert!(load("not json\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = load(OLD.as_bytes()).unwrap();
+        let new = load(NEW.as_bytes()).unwrap();
+        let lines = diff(&old, &new)
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                "- [192.168.1.10] 0288:01 removed",
+                "~ 0130:01 moved from 192.168.1.10 to 192.168.1.20",
+                "~ [192.168.1.20] 0130:01 standard version information: 00004A00 -> 00005000",
+                "~ [192.168.1.20] 0130:01 get property map: +BB",
+                "+ [192.168.1.11] 0279:01 added",
+            ]
+        );
+        assert!(diff(&old, &old).is_empty());
+    }
+}
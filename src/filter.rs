@@ -0,0 +1,161 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr},
+    str::FromStr,
+};
+
+// a /16 has 65534 hosts, taking over an hour to sweep with the default parallelism and timeout
+const MIN_SWEEP_PREFIX: u8 = 16;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+
+    // the IPv4 host addresses of the subnet, leaving out the network and broadcast addresses
+    pub fn hosts(&self) -> anyhow::Result<Vec<IpAddr>> {
+        let IpAddr::V4(net) = self.addr else {
+            anyhow::bail!("IPv6 subnets are too large to sweep");
+        };
+        if self.prefix < MIN_SWEEP_PREFIX {
+            anyhow::bail!("subnets larger than /{} are not swept", MIN_SWEEP_PREFIX);
+        }
+        let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+        let first = u32::from(net) & mask;
+        let last = first | !mask;
+        let range = if self.prefix >= 31 {
+            first..=last
+        } else {
+            first + 1..=last - 1
+        };
+        Ok(range.map(|n| IpAddr::from(Ipv4Addr::from(n))).collect())
+    }
+}
+
+impl fmt::Debug for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = anyhow::Error;
+
+    // accepts both "192.168.1.0/24" and a bare address, which is treated as a single host
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr.parse::<IpAddr>()?, Some(prefix.parse::<u8>()?)),
+            None => (s.parse::<IpAddr>()?, None),
+        };
+        let addr = addr.to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        if prefix > max {
+            anyhow::bail!("invalid prefix length");
+        }
+        Ok(Self { addr, prefix })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AddrFilter {
+    pub allow: Vec<Cidr>,
+    pub deny: Vec<Cidr>,
+}
+
+impl AddrFilter {
+    // a denied address is never permitted, and when any allowed subnet is given the address must be in one of them
+    pub fn permits(&self, ip: &IpAddr) -> bool {
+        if self.deny.iter().any(|c| c.contains(ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|c| c.contains(ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cidr_contains() {
+        let cidr: Cidr = "192.168.1.0/24".parse().unwrap();
+        assert!(cidr.contains(&"192.168.1.1".parse().unwrap()));
+        assert!(cidr.contains(&"::ffff:192.168.1.200".parse().unwrap()));
+        assert!(!cidr.contains(&"192.168.2.1".parse().unwrap()));
+        assert!(!cidr.contains(&"fe80::1".parse().unwrap()));
+
+        let cidr: Cidr = "fe80::/10".parse().unwrap();
+        assert!(cidr.contains(&"fe80::1".parse().unwrap()));
+        assert!(!cidr.contains(&"2001:db8::1".parse().unwrap()));
+
+        let cidr: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(cidr.contains(&"10.0.0.1".parse().unwrap()));
+
+        let cidr: Cidr = "10.0.0.1".parse().unwrap();
+        assert!(cidr.contains(&"10.0.0.1".parse().unwrap()));
+        assert!(!cidr.contains(&"10.0.0.2".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("10.0.0/8".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_cidr_hosts() {
+        let hosts = "192.168.1.0/24".parse::<Cidr>().unwrap().hosts().unwrap();
+        assert_eq!(hosts.len(), 254);
+        assert_eq!(hosts[0], "192.168.1.1".parse::<IpAddr>().unwrap());
+        assert_eq!(hosts[253], "192.168.1.254".parse::<IpAddr>().unwrap());
+        // a bare address is a single host
+        let hosts = "192.168.1.10".parse::<Cidr>().unwrap().hosts().unwrap();
+        assert_eq!(hosts, vec!["192.168.1.10".parse::<IpAddr>().unwrap()]);
+        assert_eq!(
+            "10.0.0.0/31"
+                .parse::<Cidr>()
+                .unwrap()
+                .hosts()
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!("10.0.0.0/8".parse::<Cidr>().unwrap().hosts().is_err());
+        assert!("fe80::/120".parse::<Cidr>().unwrap().hosts().is_err());
+    }
+
+    #[test]
+    fn test_addr_filter_permits() {
+        let filter = AddrFilter::default();
+        assert!(filter.permits(&"192.168.1.1".parse().unwrap()));
+
+        let filter = AddrFilter {
+            allow: vec!["192.168.1.0/24".parse().unwrap()],
+            deny: vec!["192.168.1.100".parse().unwrap()],
+        };
+        assert!(filter.permits(&"192.168.1.1".parse().unwrap()));
+        assert!(!filter.permits(&"192.168.1.100".parse().unwrap()));
+        assert!(!filter.permits(&"192.168.2.1".parse().unwrap()));
+
+        let filter = AddrFilter {
+            allow: vec![],
+            deny: vec!["192.168.1.0/24".parse().unwrap()],
+        };
+        assert!(filter.permits(&"192.168.2.1".parse().unwrap()));
+        assert!(!filter.permits(&"192.168.1.1".parse().unwrap()));
+    }
+}
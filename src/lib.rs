@@ -0,0 +1,66 @@
+#![allow(clippy::upper_case_acronyms)]
+
+// the protocol core, which doesn't depend on any runtime
+pub mod aggregate;
+pub mod consts;
+pub mod decoder;
+pub mod filter;
+pub mod limit;
+pub mod packet;
+pub mod response;
+pub mod transaction;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+// the tokio client and the commands built on it
+#[cfg(feature = "audit")]
+pub mod audit;
+#[cfg(feature = "tokio")]
+pub mod caps;
+#[cfg(feature = "tokio")]
+pub mod check;
+#[cfg(feature = "tokio")]
+pub mod cli;
+#[cfg(feature = "tokio")]
+pub mod client;
+#[cfg(feature = "tokio")]
+pub mod clock;
+#[cfg(feature = "tokio")]
+pub mod control;
+#[cfg(feature = "tokio")]
+pub mod diagnosis;
+#[cfg(feature = "tokio")]
+pub mod diff;
+#[cfg(feature = "tokio")]
+pub mod map;
+#[cfg(feature = "tokio")]
+pub mod meter;
+#[cfg(feature = "nats")]
+pub mod nats;
+#[cfg(feature = "tokio")]
+pub mod output;
+#[cfg(feature = "tokio")]
+pub mod ping;
+#[cfg(feature = "tokio")]
+pub mod report;
+#[cfg(feature = "tokio")]
+pub mod scan;
+#[cfg(feature = "tokio")]
+pub mod script;
+#[cfg(feature = "tokio")]
+pub mod select;
+#[cfg(feature = "tokio")]
+pub mod sink;
+#[cfg(feature = "tokio")]
+pub mod snapshot;
+#[cfg(feature = "tokio")]
+pub mod socket;
+#[cfg(feature = "tokio")]
+pub mod spool;
+#[cfg(feature = "tokio")]
+pub mod stats;
+#[cfg(feature = "tokio")]
+pub mod sweep;
+#[cfg(feature = "tokio")]
+pub mod template;
@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    // packets per second each source may send on average
+    pub rate: f64,
+    // packets each source may send at once, such as the responses to a walk
+    pub burst: f64,
+    // how long a source exceeding the limit is ignored
+    pub mute: Duration,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Pass,
+    // the first packet after the source was unmuted, with the number of the packets dropped meanwhile
+    Unmuted { dropped: u64 },
+    // the source has just exceeded the limit, which drops this packet
+    Muted,
+    Dropped,
+}
+
+struct Bucket {
+    tokens: f64,
+    at: Instant,
+    muted_until: Option<Instant>,
+    dropped: u64,
+}
+
+// a token bucket per source, which mutes the sources flooding the network rather than slowing them down
+pub struct Limiter {
+    limit: RateLimit,
+    buckets: HashMap<IpAddr, Bucket>,
+}
+
+impl Limiter {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: HashMap::new(),
+        }
+    }
+
+    pub fn limit(&self) -> RateLimit {
+        self.limit
+    }
+
+    pub fn check(&mut self, addr: IpAddr, now: Instant) -> Verdict {
+        let limit = self.limit;
+        let bucket = self.buckets.entry(addr).or_insert(Bucket {
+            tokens: limit.burst,
+            at: now,
+            muted_until: None,
+            dropped: 0,
+        });
+        let mut verdict = Verdict::Pass;
+        if let Some(until) = bucket.muted_until {
+            if now < until {
+                bucket.dropped += 1;
+                return Verdict::Dropped;
+            }
+            verdict = Verdict::Unmuted {
+                dropped: bucket.dropped,
+            };
+            // starts over with a full bucket
+            *bucket = Bucket {
+                tokens: limit.burst,
+                at: now,
+                muted_until: None,
+                dropped: 0,
+            };
+        }
+        bucket.tokens = (bucket.tokens + now.duration_since(bucket.at).as_secs_f64() * limit.rate)
+            .min(limit.burst);
+        bucket.at = now;
+        if bucket.tokens < 1.0 {
+            bucket.muted_until = Some(now + limit.mute);
+            bucket.dropped = 1;
+            return Verdict::Muted;
+        }
+        bucket.tokens -= 1.0;
+        verdict
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let now = Instant::now();
+        let addr: IpAddr = "192.168.1.10".parse().unwrap();
+        let other: IpAddr = "192.168.1.11".parse().unwrap();
+        let mut limiter = Limiter::new(RateLimit {
+            rate: 10.0,
+            burst: 3.0,
+            mute: Duration::from_secs(60),
+        });
+        for _ in 0..3 {
+            assert_eq!(limiter.check(addr, now), Verdict::Pass);
+        }
+        // refilled at the rate
+        let later = now + Duration::from_millis(100);
+        assert_eq!(limiter.check(addr, later), Verdict::Pass);
+        assert_eq!(limiter.check(addr, later), Verdict::Muted);
+        assert_eq!(limiter.check(addr, later), Verdict::Dropped);
+        // the other sources are unaffected
+        assert_eq!(limiter.check(other, later), Verdict::Pass);
+        let later = later + Duration::from_secs(59);
+        assert_eq!(limiter.check(addr, later), Verdict::Dropped);
+        let later = later + Duration::from_secs(1);
+        assert_eq!(limiter.check(addr, later), Verdict::Unmuted { dropped: 3 });
+        assert_eq!(limiter.check(addr, later), Verdict::Pass);
+    }
+}
@@ -1,94 +1,404 @@
-use log::{debug, error, info, trace, warn};
+use anyhow::Context;
+#[cfg(feature = "docs")]
+use clap::CommandFactory;
+use clap::Parser;
+#[cfg(feature = "audit")]
+use elscan::audit;
+use elscan::{
+    aggregate::Aggregator,
+    caps, check, cli, client, clock,
+    consts::{self, ECHONET_LITE_PORT, MULTICAST_ADDR_V4},
+    control, decoder, diff, filter, limit, map, meter, output, packet, ping, report, response,
+    scan, script, snapshot, socket, stats, sweep,
+};
+#[cfg(feature = "nats")]
+use elscan::{nats, spool};
+use log::{debug, error, info, warn};
 use std::{
-    net::Ipv4Addr,
-    sync::{Arc, LazyLock},
+    collections::BTreeSet,
+    fs::File,
+    io::{self, BufReader, BufWriter, IsTerminal, Write},
+    sync::Arc,
 };
-use tokio::{net::UdpSocket, time};
-
-mod packet;
-mod response;
-
-const ECHONET_LITE_PORT: u16 = 3610;
-static MULTICAST_ADDR_V4: LazyLock<Ipv4Addr> = LazyLock::new(|| "224.0.23.0".parse().unwrap());
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+    let args = cli::Cli::parse();
+    let checking = matches!(args.command, Some(cli::Command::Check { .. }));
+    let result = run(args).await;
+    // monitoring systems tell failures of the check itself by the exit code, taking 1 for a warning
+    if let (true, Err(e)) = (checking, &result) {
+        println!("ELSCAN {} - {:#}", check::Status::Unknown, e);
+        std::process::exit(check::Status::Unknown as i32);
+    }
+    result
+}
+
+async fn run(args: cli::Cli) -> anyhow::Result<()> {
+    let default_filter = if args.verbose {
+        "info,elscan=debug"
+    } else {
+        "info"
+    };
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(default_filter))
         .default_format()
         .init();
 
+    decoder::set_lang(args.lang);
+
+    // decoding doesn't need the network
+    if let Some(cli::Command::Decode { frame }) = &args.command {
+        let bytes: Vec<u8> = packet::EDT::from_hex(&frame.concat())?
+            .0
+            .iter()
+            .map(|b| b.0)
+            .collect();
+        match packet::Frame::try_from(&bytes[..])? {
+            packet::Frame::Specified(packet) => println!("{:#}", packet),
+            packet::Frame::Arbitrary { tid, payload } => println!(
+                "TID:  {:04X}\nArbitrary message format: 0x{}",
+                tid.0,
+                packet::EDT::from(payload).to_hex()
+            ),
+        }
+        return Ok(());
+    }
+    // nor does generating the documents of the commands
+    #[cfg(feature = "docs")]
+    if let Some(cli::Command::Completions { shell }) = &args.command {
+        clap_complete::generate(
+            *shell,
+            &mut cli::Cli::command(),
+            "elscan",
+            &mut io::stdout(),
+        );
+        return Ok(());
+    }
+    #[cfg(feature = "docs")]
+    if let Some(cli::Command::Man { dir }) = &args.command {
+        let command = cli::Cli::command();
+        match dir {
+            Some(dir) => clap_mangen::generate_to(command, dir)?,
+            None => clap_mangen::Man::new(command).render(&mut io::stdout())?,
+        }
+        return Ok(());
+    }
+    if let Some(cli::Command::Diff { old, new }) = &args.command {
+        let load = |path: &std::path::Path| {
+            File::open(path)
+                .map_err(anyhow::Error::from)
+                .and_then(|f| diff::load(BufReader::new(f)))
+                .with_context(|| format!("failed to read {}", path.display()))
+        };
+        let changes = diff::diff(&load(old)?, &load(new)?);
+        for change in &changes {
+            match args.output {
+                output::Format::Json => println!("{}", serde_json::to_string(change)?),
+                _ => println!("{}", change),
+            }
+        }
+        info!("{} changes", changes.len());
+        return Ok(());
+    }
+    #[cfg(feature = "audit")]
+    if let Some(cli::Command::Audit {
+        command: cli::AuditCommand::Verify { file },
+    }) = &args.command
+    {
+        let count = audit::verify(BufReader::new(File::open(file)?))
+            .with_context(|| format!("{} has been tampered with", file.display()))?;
+        println!("{}: {} records intact", file.display(), count);
+        return Ok(());
+    }
+
     info!(
         "Establishing connection... (port: {}, multicast_addr: {})",
-        ECHONET_LITE_PORT,
-        MULTICAST_ADDR_V4.to_string()
+        ECHONET_LITE_PORT, MULTICAST_ADDR_V4
     );
-    let sock = {
-        let s = UdpSocket::bind(("::", ECHONET_LITE_PORT)).await?;
-        s.set_multicast_loop_v4(false)?;
-        s.join_multicast_v4(MULTICAST_ADDR_V4.clone(), Ipv4Addr::UNSPECIFIED)?;
-        Arc::new(s)
-    };
-
-    let mut buf = [0; 1024];
-    info!("Listening ECHONET Lite packets...");
-    let sock_inner = Arc::clone(&sock);
-    tokio::spawn(async move {
-        // send discovery packet after 1 second sleep
-        time::sleep(time::Duration::from_secs(1)).await;
-        let packet = packet::Packet::new_discovery_request();
-        debug!(
-            "discover request (to: {}) {:?}",
-            MULTICAST_ADDR_V4.to_string(),
-            packet
+    let sockets = socket::Sockets::bind(&args.socket)?;
+    debug!("bound to port {}", sockets.local_port()?);
+    let mut client = client::Client::new(
+        sockets.unicast.clone(),
+        filter::AddrFilter::from(&args.filter),
+        args.write_interval.try_into()?,
+        args.max_outstanding.into(),
+        args.strict,
+    );
+    if args.inbound_rate > 0 {
+        client = client.with_rate_limit(limit::RateLimit {
+            rate: args.inbound_rate.into(),
+            burst: args.inbound_burst.into(),
+            mute: args.mute_duration.try_into()?,
+        });
+    }
+    #[cfg(feature = "audit")]
+    if let Some(path) = &args.audit_log {
+        let actor = std::env::var("USER")
+            .or_else(|_| std::env::var("LOGNAME"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        client = client.with_audit(
+            audit::Audit::open(path, actor)
+                .with_context(|| format!("failed to open {}", path.display()))?,
+        );
+    }
+    let mut output = output::Output::new(args.output, args.output_queue.try_into()?);
+    if let Some(selector) = args.selector {
+        output = output.with_selector(selector);
+    }
+    if let Some(gateway) = args.gateway_id {
+        output = output.with_gateway(gateway);
+    }
+    #[cfg(feature = "nats")]
+    if let Some(mut server) = args.nats {
+        if let Some(path) = &args.nats_credentials {
+            server.auth = Some(
+                nats::Auth::read(path)
+                    .with_context(|| format!("failed to read {}", path.display()))?,
+            );
+        }
+        let spool = match &args.spool_dir {
+            Some(dir) => {
+                std::fs::create_dir_all(dir)?;
+                let path = dir.join("nats.spool");
+                let spool = spool::Spool::open(&path, args.spool_size * 1024 * 1024)
+                    .with_context(|| format!("failed to open {}", path.display()))?;
+                Some(spool)
+            }
+            None => None,
+        };
+        output = output.with_nats(
+            server,
+            args.nats_subject,
+            args.output_queue.try_into()?,
+            spool,
+        );
+    }
+    let output = Arc::new(output);
+    let client = Arc::new(client.with_output(Arc::clone(&output)));
+    let aggregator = Arc::new(Aggregator::default());
+    if let Some(addr) = args.metrics_addr {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let (client, output, aggregator) = (
+            Arc::clone(&client),
+            Arc::clone(&output),
+            Arc::clone(&aggregator),
         );
-        let bytes = packet.to_bytes();
-        let result = sock_inner
-            .send_to(&bytes, (MULTICAST_ADDR_V4.to_string(), ECHONET_LITE_PORT))
-            .await;
-        if let Err(e) = result {
-            error!("Failed to send a packet: {:?}", e);
+        tokio::spawn(async move {
+            if let Err(e) = stats::serve(client, output, aggregator, listener).await {
+                error!("Stopped serving metrics: {:?}", e);
+            }
+        });
+    }
+    if let Some(interval) = args.heartbeat {
+        let interval: std::time::Duration = interval.try_into()?;
+        if interval.is_zero() {
+            anyhow::bail!("the heartbeat interval must be positive");
         }
+        let (client, output) = (Arc::clone(&client), Arc::clone(&output));
+        tokio::spawn(stats::heartbeat(client, output, interval));
+    }
+    let mut rx = sockets.receive();
+
+    let command = args.command.unwrap_or(cli::Command::Scan {
+        poll_interval: None,
+        poll_template: vec![],
+        progress: false,
     });
-    loop {
-        tokio::select! {
-            res = sock.recv_from(&mut buf) => {
-                let (msg, addr) = match res {
-                    Ok((len, addr)) => (&buf[..len], addr),
-                    Err(e) => {
-                        error!("Failed to receive a packet: {:?}", e);
-                        continue;
-                    }
-                };
-                trace!("{:?} {:?}", addr, msg);
-                let ipv4 = addr.ip().to_canonical();
-                match packet::Packet::try_from(msg) {
-                    Ok(packet) => {
-                        debug!("[{}] {:?}", ipv4, packet);
-                        if let Ok(r) = response::DiscoveryResponse::try_from(&packet) {
-                            info!("[{}] {:?}", ipv4, r);
-                            for eoj in r.instances {
-                                let packet = packet::Packet::new_sync_request(eoj);
-                                debug!("sync request (to: {}, eoj: {:?}) {:?}", ipv4, eoj, packet);
-                                let bytes = packet.to_bytes();
-                                trace!("{}", bytes.iter().map(|b| format!("{:02X}", b)).collect::<String>());
-                                if let Err(e) = sock.send_to(&bytes, (ipv4, ECHONET_LITE_PORT)).await {
-                                    error!("failed to send a packet (to: {}, eoj: {:?}) {:?}", ipv4, eoj, e);
-                                }
-                            }
-                        } else if let Ok(r) = response::SyncResponse::try_from(&packet) {
-                            info!("[{}] {:?}", ipv4, r);
-                        } else {
-                            warn!(
-                                "[{}] Received an unknown packet: {:?}",
-                                ipv4, packet
-                            );
-                        }
-                    }
-                    Err(e) => {
-                        error!("[{}] Failed to parse a packet: {:?}", ipv4, e);
+    if let cli::Command::Scan {
+        poll_interval,
+        poll_template,
+        progress,
+    } = command
+    {
+        let poll_interval = poll_interval.map(TryInto::try_into).transpose()?;
+        let mut scanner = scan::Scanner::new(client, Arc::clone(&output), poll_interval)
+            .with_templates(poll_template)
+            .with_aggregator(aggregator);
+        if progress || (args.output == output::Format::Log && io::stderr().is_terminal()) {
+            scanner = scanner.with_progress();
+        }
+        let result = Arc::new(scanner).run(rx).await;
+        output.close();
+        return result;
+    }
+    if let cli::Command::Map { format, wait, file } = command {
+        let nodes = scan::discover(client, rx, wait.try_into()?).await?;
+        let rendered = map::render(&nodes, format);
+        match &file {
+            Some(path) => std::fs::write(path, rendered)?,
+            None => print!("{}", rendered),
+        }
+        info!("Mapped {} nodes", nodes.len());
+        return Ok(());
+    }
+    if let cli::Command::Report {
+        format,
+        wait,
+        hosts,
+        file,
+    } = command
+    {
+        let report = report::collect(client, rx, wait.try_into()?, &hosts).await?;
+        let rendered = report.render(format);
+        match &file {
+            Some(path) => std::fs::write(path, rendered)?,
+            None => print!("{}", rendered),
+        }
+        info!(
+            "Reported {} nodes, {} unreachable",
+            report.nodes.len(),
+            report.unreachable.len()
+        );
+        return Ok(());
+    }
+
+    // the other commands only wait for the responses to their own requests
+    let client_inner = Arc::clone(&client);
+    tokio::spawn(async move {
+        while let Some((msg, addr)) = rx.recv().await {
+            match client_inner.receive(&msg, addr) {
+                // SetI is only answered when refused
+                Some((ipv4, packet)) if packet.esv == packet::ESV::SetISNA => {
+                    for e in response::write_errors(&packet) {
+                        warn!(
+                            "[{}] {}: {}",
+                            ipv4,
+                            packet.seoj,
+                            control::describe(packet.seoj.class(), &e)
+                        );
                     }
                 }
+                Some((ipv4, packet)) => {
+                    debug!("[{}] Ignored an unexpected packet: {}", ipv4, packet);
+                }
+                None => {}
             }
         }
-    }
+    });
+    let result = match command {
+        cli::Command::Scan { .. }
+        | cli::Command::Decode { .. }
+        | cli::Command::Diff { .. }
+        | cli::Command::Map { .. }
+        | cli::Command::Report { .. } => {
+            unreachable!()
+        }
+        #[cfg(feature = "audit")]
+        cli::Command::Audit { .. } => unreachable!(),
+        #[cfg(feature = "docs")]
+        cli::Command::Completions { .. } | cli::Command::Man { .. } => unreachable!(),
+        cli::Command::Meter {
+            command:
+                cli::MeterCommand::History {
+                    addr,
+                    days,
+                    instance,
+                },
+        } => {
+            let eoj = packet::EOJ::new(decoder::SMART_METER, instance);
+            for sample in meter::history(&client, addr, eoj, days).await? {
+                output.emit(&output::Event::MeterHistory { addr, eoj, sample });
+            }
+            Ok(())
+        }
+        cli::Command::Caps { addr, eoj } => caps::run(&client, addr, eoj).await,
+        cli::Command::Clock {
+            addr,
+            eoj,
+            set_from_host,
+        } => clock::run(&client, &output, addr, eoj, set_from_host).await,
+        cli::Command::Snapshot { addr, eoj, file } => {
+            let snapshot = snapshot::take(&client, addr, eoj).await?;
+            let mut writer: Box<dyn Write> = match &file {
+                Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+                None => Box::new(io::stdout()),
+            };
+            serde_json::to_writer_pretty(&mut writer, &snapshot)?;
+            writeln!(writer)?;
+            info!(
+                "Saved {} properties of {:?}",
+                snapshot.props.len(),
+                snapshot.eoj
+            );
+            Ok(())
+        }
+        cli::Command::Restore { file, addr } => {
+            let snapshot: snapshot::Snapshot =
+                serde_json::from_reader(BufReader::new(File::open(file)?))?;
+            let addr = addr.unwrap_or(snapshot.addr);
+            snapshot::restore(&client, &output, addr, &snapshot).await
+        }
+        cli::Command::Get { addr, eoj, epcs } => {
+            control::get(&client, &output, addr, eoj, &epcs).await
+        }
+        cli::Command::Set {
+            addr,
+            eoj,
+            mut props,
+            values,
+            no_confirm,
+        } => {
+            props.extend(control::encode_values(eoj.class(), &values)?);
+            if no_confirm {
+                control::set_no_confirm(&client, addr, eoj, props).await
+            } else {
+                control::set(&client, addr, eoj, props).await
+            }
+        }
+        cli::Command::Sweep {
+            subnets,
+            parallelism,
+            timeout,
+        } => {
+            let mut hosts = BTreeSet::new();
+            for subnet in &subnets {
+                hosts.extend(subnet.hosts()?);
+            }
+            sweep::run(
+                client,
+                Arc::clone(&output),
+                hosts.into_iter().collect(),
+                parallelism.into(),
+                timeout.try_into()?,
+            )
+            .await
+        }
+        cli::Command::Check {
+            devices,
+            warn_rtt,
+            crit_rtt,
+        } => {
+            let crit_rtt = crit_rtt.map(TryInto::try_into).transpose()?;
+            let outcomes = check::probe(Arc::clone(&client), &devices).await;
+            let (status, line) = check::evaluate(&outcomes, warn_rtt.try_into()?, crit_rtt);
+            println!("{}", line);
+            std::process::exit(status as i32);
+        }
+        cli::Command::Ping {
+            addr,
+            eoj,
+            count,
+            interval,
+            timeout,
+        } => {
+            ping::run(
+                &client,
+                addr,
+                eoj.unwrap_or(consts::eoj::NODE_PROFILE),
+                count,
+                interval.try_into()?,
+                timeout.try_into()?,
+            )
+            .await
+        }
+        cli::Command::Run {
+            file,
+            continue_on_error,
+        } => {
+            let steps = script::parse(&std::fs::read_to_string(file)?)?;
+            script::run(&client, &output, steps, continue_on_error).await
+        }
+    };
+    output.close();
+    result
 }
@@ -0,0 +1,158 @@
+use crate::{consts::eoj, decoder, packet::EOJ};
+use clap::ValueEnum;
+use std::{collections::BTreeMap, fmt::Write, net::IpAddr};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    // Graphviz
+    Dot,
+    Mermaid,
+}
+
+// the device objects of the node grouped by class, the node profile being left out
+fn classes(instances: &[EOJ]) -> BTreeMap<u16, Vec<EOJ>> {
+    let mut classes = BTreeMap::<u16, Vec<EOJ>>::new();
+    for &eoj in instances {
+        if eoj.class() != decoder::NODE_PROFILE {
+            classes.entry(eoj.class()).or_default().push(eoj);
+        }
+    }
+    for instances in classes.values_mut() {
+        instances.sort_by_key(|e| e.instance());
+    }
+    classes
+}
+
+fn class_label(class: u16) -> String {
+    match decoder::class_name(class) {
+        Some(name) => format!("{} ({:04X})", name, class),
+        None => format!("{:04X}", class),
+    }
+}
+
+// the controller links to the node profile of every node, which hosts the instances of the node
+pub fn render(nodes: &BTreeMap<IpAddr, Vec<EOJ>>, format: Format) -> String {
+    match format {
+        Format::Dot => to_dot(nodes),
+        Format::Mermaid => to_mermaid(nodes),
+    }
+}
+
+pub fn to_dot(nodes: &BTreeMap<IpAddr, Vec<EOJ>>) -> String {
+    let mut s = String::new();
+    writeln!(s, "digraph echonet {{").unwrap();
+    writeln!(s, "  rankdir=LR;").unwrap();
+    writeln!(s, "  node [shape=box];").unwrap();
+    writeln!(s, "  controller [label=\"elscan\\n{}\"];", eoj::CONTROLLER).unwrap();
+    for (addr, instances) in nodes {
+        let profile = format!("{}/{}", addr, eoj::NODE_PROFILE);
+        writeln!(s, "  subgraph \"cluster_{}\" {{", addr).unwrap();
+        writeln!(s, "    label=\"{}\";", addr).unwrap();
+        writeln!(
+            s,
+            "    \"{}\" [label=\"{}\\nnode-profile\"];",
+            profile,
+            eoj::NODE_PROFILE
+        )
+        .unwrap();
+        for (class, instances) in classes(instances) {
+            writeln!(s, "    subgraph \"cluster_{}/{:04X}\" {{", addr, class).unwrap();
+            writeln!(s, "      label=\"{}\";", class_label(class)).unwrap();
+            for eoj in &instances {
+                writeln!(s, "      \"{}/{}\" [label=\"{}\"];", addr, eoj, eoj).unwrap();
+            }
+            writeln!(s, "    }}").unwrap();
+            for eoj in &instances {
+                writeln!(s, "    \"{}\" -> \"{}/{}\";", profile, addr, eoj).unwrap();
+            }
+        }
+        writeln!(s, "  }}").unwrap();
+        writeln!(s, "  controller -> \"{}\";", profile).unwrap();
+    }
+    writeln!(s, "}}").unwrap();
+    s
+}
+
+// node ids are numbered since mermaid doesn't accept addresses as they are
+pub fn to_mermaid(nodes: &BTreeMap<IpAddr, Vec<EOJ>>) -> String {
+    let mut s = String::new();
+    writeln!(s, "flowchart LR").unwrap();
+    writeln!(s, "  controller[\"elscan<br>{}\"]", eoj::CONTROLLER).unwrap();
+    let mut links = vec![];
+    let mut edges = vec![];
+    for (n, (addr, instances)) in nodes.iter().enumerate() {
+        let profile = format!("n{}_profile", n);
+        writeln!(s, "  subgraph n{}[\"{}\"]", n, addr).unwrap();
+        writeln!(
+            s,
+            "    {}[\"{}<br>node-profile\"]",
+            profile,
+            eoj::NODE_PROFILE
+        )
+        .unwrap();
+        for (class, instances) in classes(instances) {
+            writeln!(
+                s,
+                "    subgraph n{}_{:04X}[\"{}\"]",
+                n,
+                class,
+                class_label(class)
+            )
+            .unwrap();
+            for eoj in instances {
+                let id = format!("n{}_{:04X}_{:02X}", n, class, eoj.instance());
+                writeln!(s, "      {}[\"{}\"]", id, eoj).unwrap();
+                edges.push(format!("{} --> {}", profile, id));
+            }
+            writeln!(s, "    end").unwrap();
+        }
+        writeln!(s, "  end").unwrap();
+        links.push(format!("controller --> {}", profile));
+    }
+    for edge in links.iter().chain(&edges) {
+        writeln!(s, "  {}", edge).unwrap();
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes() -> BTreeMap<IpAddr, Vec<EOJ>> {
+        BTreeMap::from([(
+            "192.168.1.10".parse().unwrap(),
+            vec![
+                EOJ::new(0x0130, 2),
+                EOJ::new(0x0130, 1),
+                EOJ::new(0x0288, 1),
+                EOJ::new(0x0EF0, 1),
+            ],
+        )])
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let dot = to_dot(&nodes());
+        assert!(dot.contains(concat!(
+            "    subgraph \"cluster_192.168.1.10/0130\" {\n",
+            "      label=\"aircon (0130)\";\n",
+            "      \"192.168.1.10/0130:01\" [label=\"0130:01\"];\n",
+            "      \"192.168.1.10/0130:02\" [label=\"0130:02\"];\n",
+            "    }\n",
+        )));
+        assert!(dot.contains("    \"192.168.1.10/0EF0:01\" -> \"192.168.1.10/0288:01\";\n"));
+        assert!(dot.contains("  controller -> \"192.168.1.10/0EF0:01\";\n"));
+        // the node profile isn't listed as an instance of itself
+        assert!(!dot.contains("cluster_192.168.1.10/0EF0"));
+    }
+
+    #[test]
+    fn test_to_mermaid() {
+        let mermaid = to_mermaid(&nodes());
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains("    subgraph n0_0288[\"smart-meter (0288)\"]\n"));
+        assert!(mermaid.contains("      n0_0130_02[\"0130:02\"]\n"));
+        assert!(mermaid.contains("  controller --> n0_profile\n  n0_profile --> n0_0130_01\n"));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{
+    client::Client,
+    consts::epc::smart_meter::{COEFFICIENT, ENERGY_HISTORY, ENERGY_UNIT, HISTORY_DAY},
+    decoder::{self, Props},
+    packet::{Packet, EDT, EOJ},
+};
+use jiff::{civil::Date, tz::TimeZone, Timestamp, ToSpan, Zoned};
+use serde::Serialize;
+use std::net::IpAddr;
+
+// the number of cumulative amounts collected every 30 minutes in a day
+const SAMPLES_PER_DAY: usize = 48;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistorySample {
+    pub timestamp: Timestamp,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<&'static str>,
+    pub raw: EDT,
+}
+
+// reads the cumulative amounts of the last `days` days including today, in chronological order
+pub async fn history(
+    client: &Client,
+    addr: IpAddr,
+    eoj: EOJ,
+    days: u8,
+) -> anyhow::Result<Vec<HistorySample>> {
+    let res = client
+        .request(
+            addr,
+            Packet::new_get_request(eoj, &[COEFFICIENT, ENERGY_UNIT])?,
+        )
+        .await?;
+    let props = res.to_props();
+
+    // the days are counted back from the date of the meter, which is assumed to be synchronized with the host
+    let today = Zoned::now().date();
+    let tz = TimeZone::system();
+    let mut samples = Vec::with_capacity(days as usize * SAMPLES_PER_DAY);
+    for day in (0..days).rev() {
+        let res = client
+            .request(
+                addr,
+                Packet::new_set_request(eoj, vec![(HISTORY_DAY, EDT::from(vec![day]))])?,
+            )
+            .await?;
+        if !res.is_normal_response() {
+            anyhow::bail!("failed to select the day {} of the historical data", day);
+        }
+        let res = client
+            .request(addr, Packet::new_get_request(eoj, &[ENERGY_HISTORY])?)
+            .await?;
+        let Some(edt) = res.to_props().remove(&ENERGY_HISTORY) else {
+            anyhow::bail!("not found historical data");
+        };
+        let date = today.checked_sub((day as i64).days())?;
+        samples.extend(parse_history(&edt, day, date, &tz, &props)?);
+    }
+    Ok(samples)
+}
+
+fn parse_history(
+    edt: &EDT,
+    day: u8,
+    date: Date,
+    tz: &TimeZone,
+    props: &Props,
+) -> anyhow::Result<Vec<HistorySample>> {
+    // the first 2 bytes echo the day, followed by the amounts collected at 0:00, 0:30, ..., 23:30
+    if edt.0.len() != 2 + 4 * SAMPLES_PER_DAY {
+        anyhow::bail!("invalid historical data");
+    }
+    if u16::from_be_bytes([edt.0[0].0, edt.0[1].0]) != day as u16 {
+        anyhow::bail!("historical data of another day");
+    }
+    edt.0[2..]
+        .chunks(4)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let timestamp = date
+                .at((i / 2) as i8, (i % 2 * 30) as i8, 0, 0)
+                .to_zoned(tz.clone())?
+                .timestamp();
+            let n = chunk.iter().fold(0, |acc, b| (acc << 8) | b.0 as u64);
+            // 0xFFFFFFFE means that the amount is not collected
+            let (value, unit) = match decoder::meter_energy(n, props) {
+                Some((value, unit, _)) if n != 0xFFFF_FFFE => (Some(value), unit),
+                _ => (None, None),
+            };
+            Ok(HistorySample {
+                timestamp,
+                value,
+                unit,
+                raw: EDT(chunk.into()),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_history() {
+        let mut data = vec![0x00, 0x01];
+        for i in 0..SAMPLES_PER_DAY as u32 {
+            let n = if i < 47 { 1000 + i } else { 0xFFFF_FFFE };
+            data.extend_from_slice(&n.to_be_bytes());
+        }
+        let edt = EDT::from(data);
+        let props = Props::from([(ENERGY_UNIT, EDT::from(vec![0x01]))]);
+        let date = jiff::civil::date(2024, 12, 31);
+
+        let samples = parse_history(&edt, 1, date, &TimeZone::UTC, &props).unwrap();
+        assert_eq!(samples.len(), SAMPLES_PER_DAY);
+        assert_eq!(
+            samples[0],
+            HistorySample {
+                timestamp: "2024-12-31T00:00:00Z".parse().unwrap(),
+                value: Some(100.0),
+                unit: Some("kWh"),
+                raw: EDT::from(vec![0x00, 0x00, 0x03, 0xE8]),
+            }
+        );
+        assert_eq!(
+            samples[3].timestamp,
+            "2024-12-31T01:30:00Z".parse::<Timestamp>().unwrap()
+        );
+        assert_eq!(samples[3].value, Some(100.3));
+        assert_eq!(
+            samples[47],
+            HistorySample {
+                timestamp: "2024-12-31T23:30:00Z".parse().unwrap(),
+                value: None,
+                unit: None,
+                raw: EDT::from(vec![0xFF, 0xFF, 0xFF, 0xFE]),
+            }
+        );
+
+        assert!(parse_history(&edt, 0, date, &TimeZone::UTC, &props).is_err());
+        assert!(parse_history(
+            &EDT::from(vec![0x00, 0x01]),
+            1,
+            date,
+            &TimeZone::UTC,
+            &props
+        )
+        .is_err());
+    }
+}
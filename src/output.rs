@@ -0,0 +1,679 @@
+use crate::{
+    aggregate::HouseholdPower,
+    decoder::{PropertyValue, Props},
+    diagnosis::Diagnosis,
+    meter::HistorySample,
+    packet::{ElU8, EOJ},
+    response::{BootAnnouncement, DiscoveryResponse, NodeProfile, SyncResponse},
+    scan::Progress,
+    select::Selector,
+    sink::{Sink, SinkStats, Writer},
+    snapshot::RestoreResult,
+};
+#[cfg(feature = "nats")]
+use crate::{nats, spool::Spool};
+use clap::ValueEnum;
+use jiff::{civil::DateTime, Timestamp};
+use log::{error, info, warn};
+use serde::{Serialize, Serializer};
+use std::{
+    collections::HashMap,
+    io,
+    net::IpAddr,
+    sync::{Mutex, Once},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    // human-readable log lines on stderr
+    Log,
+    // one JSON object per line on stdout
+    Json,
+    // one row per property on stdout, the other events being logged
+    Csv,
+    // a sequence of CBOR items on stdout, one per event
+    #[cfg(feature = "cbor")]
+    Cbor,
+    // a stream of MessagePack maps on stdout, one per event
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Discovery {
+        #[serde(serialize_with = "as_str")]
+        addr: IpAddr,
+        #[serde(flatten)]
+        response: DiscoveryResponse,
+    },
+    Sync {
+        #[serde(serialize_with = "as_str")]
+        addr: IpAddr,
+        #[serde(flatten)]
+        response: SyncResponse,
+    },
+    NodeProfile {
+        #[serde(serialize_with = "as_str")]
+        addr: IpAddr,
+        #[serde(flatten)]
+        profile: NodeProfile,
+    },
+    Reboot {
+        #[serde(serialize_with = "as_str")]
+        addr: IpAddr,
+        #[serde(flatten)]
+        announcement: BootAnnouncement,
+    },
+    Property {
+        #[serde(serialize_with = "as_str")]
+        addr: IpAddr,
+        eoj: EOJ,
+        epc: ElU8,
+        #[serde(flatten)]
+        value: PropertyValue,
+    },
+    MeterHistory {
+        #[serde(serialize_with = "as_str")]
+        addr: IpAddr,
+        eoj: EOJ,
+        #[serde(flatten)]
+        sample: HistorySample,
+    },
+    Household(HouseholdPower),
+    Clock {
+        #[serde(serialize_with = "as_str")]
+        addr: IpAddr,
+        eoj: EOJ,
+        datetime: DateTime,
+        host: DateTime,
+        // seconds the clock of the device is ahead of the host
+        drift: i64,
+    },
+    Restore {
+        #[serde(serialize_with = "as_str")]
+        addr: IpAddr,
+        eoj: EOJ,
+        epc: ElU8,
+        result: RestoreResult,
+    },
+    Progress(Progress),
+    // an object which didn't answer, with what its host was found to do
+    Timeout {
+        #[serde(serialize_with = "as_str")]
+        addr: IpAddr,
+        eoj: EOJ,
+        #[serde(flatten)]
+        diagnosis: Diagnosis,
+    },
+    // a source exceeding the inbound rate, whose packets are ignored for the duration
+    Muted {
+        #[serde(serialize_with = "as_str")]
+        addr: IpAddr,
+        // packets per second and at once
+        rate: f64,
+        burst: f64,
+        // seconds
+        duration: u64,
+    },
+    // a muted source whose packets are taken again, with the number of the ones dropped meanwhile
+    Unmuted {
+        #[serde(serialize_with = "as_str")]
+        addr: IpAddr,
+        dropped: u64,
+    },
+    // tells a collector the gateway is alive
+    Heartbeat {
+        // seconds since the start
+        uptime: u64,
+        packets_sent: u64,
+        packets_received: u64,
+        // the addresses heard from so far
+        sources: usize,
+        dropped_records: u64,
+    },
+}
+
+impl Event {
+    // the tag of the event in the serialized forms
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Discovery { .. } => "discovery",
+            Self::Sync { .. } => "sync",
+            Self::NodeProfile { .. } => "node_profile",
+            Self::Reboot { .. } => "reboot",
+            Self::Property { .. } => "property",
+            Self::MeterHistory { .. } => "meter_history",
+            Self::Household(_) => "household",
+            Self::Clock { .. } => "clock",
+            Self::Restore { .. } => "restore",
+            Self::Progress(_) => "progress",
+            Self::Timeout { .. } => "timeout",
+            Self::Muted { .. } => "muted",
+            Self::Unmuted { .. } => "unmuted",
+            Self::Heartbeat { .. } => "heartbeat",
+        }
+    }
+
+    pub fn addr(&self) -> Option<IpAddr> {
+        match self {
+            Self::Discovery { addr, .. }
+            | Self::Sync { addr, .. }
+            | Self::NodeProfile { addr, .. }
+            | Self::Reboot { addr, .. }
+            | Self::Property { addr, .. }
+            | Self::MeterHistory { addr, .. }
+            | Self::Clock { addr, .. }
+            | Self::Restore { addr, .. }
+            | Self::Timeout { addr, .. }
+            | Self::Muted { addr, .. }
+            | Self::Unmuted { addr, .. } => Some(*addr),
+            Self::Household(_) | Self::Progress(_) | Self::Heartbeat { .. } => None,
+        }
+    }
+
+    // the object the event is about
+    pub fn eoj(&self) -> Option<EOJ> {
+        match self {
+            Self::Discovery { response, .. } => Some(response.eoj),
+            Self::Sync { response, .. } => Some(response.eoj),
+            Self::NodeProfile { profile, .. } => Some(profile.eoj),
+            Self::Reboot { announcement, .. } => Some(announcement.eoj),
+            Self::Property { eoj, .. }
+            | Self::MeterHistory { eoj, .. }
+            | Self::Clock { eoj, .. }
+            | Self::Restore { eoj, .. }
+            | Self::Timeout { eoj, .. } => Some(*eoj),
+            Self::Household(_)
+            | Self::Progress(_)
+            | Self::Muted { .. }
+            | Self::Unmuted { .. }
+            | Self::Heartbeat { .. } => None,
+        }
+    }
+}
+
+// addresses are written as text even in the binary formats, whose serializers would take them apart
+fn as_str<S: Serializer>(addr: &IpAddr, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(addr)
+}
+
+// the event with the gateway it comes from, for the collectors of many gateways
+#[derive(Serialize)]
+struct Stamped<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gateway: Option<&'a str>,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+// gateway IDs go into NATS subjects and Prometheus labels as they are
+pub fn parse_gateway_id(s: &str) -> anyhow::Result<String> {
+    if s.is_empty()
+        || !s
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        anyhow::bail!("expected letters, digits, '-' and '_'");
+    }
+    Ok(s.to_string())
+}
+
+const CSV_HEADER: &str = "timestamp,addr,eoj,epc,name,value,unit,raw";
+
+pub struct Output {
+    format: Format,
+    csv_header: Once,
+    // the log lines go through the logger instead
+    stdout: Option<Writer>,
+    // the subject the JSON events are published under, followed by the event names
+    #[cfg(feature = "nats")]
+    nats: Option<(Writer, String)>,
+    // the log and the sinks registered by the embedders
+    sinks: Vec<Writer<Event>>,
+    gateway: Option<String>,
+    selector: Option<Selector>,
+    // the latest properties of each object, which the selector refers to
+    props: Mutex<HashMap<(IpAddr, EOJ), Props>>,
+}
+
+impl Output {
+    // up to queue_size events wait to be written before the newer ones are dropped
+    pub fn new(format: Format, queue_size: usize) -> Self {
+        Self {
+            format,
+            csv_header: Once::new(),
+            stdout: (format != Format::Log)
+                .then(|| Writer::spawn("stdout", queue_size, io::stdout())),
+            #[cfg(feature = "nats")]
+            nats: None,
+            sinks: match format {
+                Format::Log => vec![Writer::spawn_sink("log", queue_size, LogSink)],
+                _ => vec![],
+            },
+            gateway: None,
+            selector: None,
+            props: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // outputs only the events the selector matches
+    pub fn with_selector(mut self, selector: Selector) -> Self {
+        self.selector = Some(selector);
+        self
+    }
+
+    // publishes the events in JSON to NATS as well, whatever the output format is, keeping them in
+    // the spool while the server is unreachable
+    #[cfg(feature = "nats")]
+    pub fn with_nats(
+        mut self,
+        server: nats::Server,
+        subject: String,
+        queue_size: usize,
+        spool: Option<Spool>,
+    ) -> Self {
+        let conn = nats::Connection::new(server);
+        let sink = match spool {
+            Some(spool) => Writer::spawn_spooled("nats", queue_size, conn, spool),
+            None => Writer::spawn("nats", queue_size, conn),
+        };
+        self.nats = Some((sink, subject));
+        self
+    }
+
+    // hands the events to the sink as well, up to queue_size of them waiting at a time
+    pub fn with_sink(mut self, name: &'static str, queue_size: usize, sink: impl Sink) -> Self {
+        self.sinks.push(Writer::spawn_sink(name, queue_size, sink));
+        self
+    }
+
+    // stamps the events, the NATS subjects and the CSV rows with the gateway
+    pub fn with_gateway(mut self, gateway: String) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    pub fn gateway(&self) -> Option<&str> {
+        self.gateway.as_deref()
+    }
+
+    pub fn emit(&self, event: &Event) {
+        if !self.selects(event) {
+            return;
+        }
+        let stamped = Stamped {
+            gateway: self.gateway(),
+            event,
+        };
+        #[cfg(feature = "nats")]
+        if let Some((sink, subject)) = &self.nats {
+            let subject = match self.gateway() {
+                Some(gateway) => format!("{}.{}.{}", subject, gateway, event.name()),
+                None => format!("{}.{}", subject, event.name()),
+            };
+            match serde_json::to_vec(&stamped) {
+                Ok(json) => sink.send(nats::publish(&subject, &json)),
+                Err(e) => error!("Failed to serialize an event: {:?}", e),
+            }
+        }
+        for sink in &self.sinks {
+            sink.send(event.clone());
+        }
+        match self.format {
+            // written by the log sink
+            Format::Log => {}
+            Format::Json => match serde_json::to_vec(&stamped) {
+                Ok(mut bytes) => {
+                    bytes.push(b'\n');
+                    self.write(bytes);
+                }
+                Err(e) => error!("Failed to serialize an event: {:?}", e),
+            },
+            #[cfg(feature = "cbor")]
+            Format::Cbor => match self.encode(&stamped) {
+                Ok(bytes) => self.write(bytes),
+                Err(e) => error!("Failed to serialize an event: {:?}", e),
+            },
+            #[cfg(feature = "msgpack")]
+            Format::Msgpack => match self.encode(&stamped) {
+                Ok(bytes) => self.write(bytes),
+                Err(e) => error!("Failed to serialize an event: {:?}", e),
+            },
+            Format::Csv => match event {
+                Event::Property {
+                    addr,
+                    eoj,
+                    epc,
+                    value,
+                } => {
+                    // the gateway column leads only when there's a gateway
+                    let prefix = self
+                        .gateway()
+                        .map(|gateway| format!("{},", gateway))
+                        .unwrap_or_default();
+                    self.csv_header.call_once(|| {
+                        let column = if self.gateway.is_some() {
+                            "gateway,"
+                        } else {
+                            ""
+                        };
+                        self.write(format!("{}{}\n", column, CSV_HEADER).into_bytes())
+                    });
+                    let row = csv_row(Timestamp::now(), *addr, *eoj, *epc, value);
+                    self.write(format!("{}{}\n", prefix, row).into_bytes());
+                }
+                _ => log(event),
+            },
+        }
+    }
+
+    // writes out the events still queued, which is needed before exiting
+    pub fn close(&self) {
+        if let Some(sink) = &self.stdout {
+            sink.close();
+        }
+        #[cfg(feature = "nats")]
+        if let Some((sink, _)) = &self.nats {
+            sink.close();
+        }
+        for sink in &self.sinks {
+            sink.close();
+        }
+    }
+
+    pub fn sink_stats(&self) -> Vec<SinkStats> {
+        let mut stats: Vec<SinkStats> = self.stdout.iter().map(Writer::stats).collect();
+        #[cfg(feature = "nats")]
+        stats.extend(self.nats.iter().map(|(sink, _)| sink.stats()));
+        stats.extend(self.sinks.iter().map(Writer::stats));
+        stats
+    }
+}
+
+impl Output {
+    fn selects(&self, event: &Event) -> bool {
+        let Some(selector) = &self.selector else {
+            return true;
+        };
+        let mut objects = self.props.lock().unwrap();
+        let props = match (event.addr(), event.eoj()) {
+            (Some(addr), Some(eoj)) => objects.entry((addr, eoj)).or_default(),
+            _ => return selector.matches(event, &Props::new()),
+        };
+        if let Event::Property { epc, value, .. } = event {
+            props.insert(*epc, value.raw.clone());
+        }
+        selector.matches(event, props)
+    }
+
+    fn write(&self, record: Vec<u8>) {
+        if let Some(sink) = &self.stdout {
+            sink.send(record);
+        }
+    }
+
+    #[cfg(any(feature = "cbor", feature = "msgpack"))]
+    fn encode(&self, event: &impl Serialize) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = vec![];
+        match self.format {
+            #[cfg(feature = "cbor")]
+            Format::Cbor => ciborium::into_writer(event, &mut bytes)?,
+            // with the field names so that the events read the same as in JSON
+            #[cfg(feature = "msgpack")]
+            Format::Msgpack => {
+                event.serialize(&mut rmp_serde::Serializer::new(&mut bytes).with_struct_map())?
+            }
+            _ => unreachable!(),
+        }
+        Ok(bytes)
+    }
+}
+
+fn csv_row(
+    timestamp: Timestamp,
+    addr: IpAddr,
+    eoj: EOJ,
+    epc: ElU8,
+    value: &PropertyValue,
+) -> String {
+    [
+        timestamp.to_string(),
+        addr.to_string(),
+        eoj.to_string(),
+        format!("{:?}", epc),
+        value.name.unwrap_or_default().to_string(),
+        value
+            .value
+            .as_ref()
+            .map(|v| v.to_string())
+            .unwrap_or_default(),
+        value.unit.unwrap_or_default().to_string(),
+        value.raw.to_hex(),
+    ]
+    .iter()
+    // fields are quoted only when they contain a separator, a quote, or a line break
+    .map(|f| {
+        if f.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", f.replace('"', "\"\""))
+        } else {
+            f.to_string()
+        }
+    })
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+// the events as log lines, which the log format writes
+pub struct LogSink;
+
+impl Sink for LogSink {
+    async fn handle(&mut self, event: Event) -> anyhow::Result<()> {
+        log(&event);
+        Ok(())
+    }
+}
+
+fn log(event: &Event) {
+    match event {
+        Event::Discovery { addr, response } => info!("[{}] {:?}", addr, response),
+        Event::Sync { addr, response } => info!("[{}] {:?}", addr, response),
+        Event::NodeProfile { addr, profile } => {
+            info!(
+                "[{}] {:?} instances: {:?}, classes: {:?}",
+                addr, profile.eoj, profile.instances, profile.classes
+            );
+            for inconsistency in &profile.inconsistencies {
+                warn!("[{}] Inconsistent node profile: {:?}", addr, inconsistency);
+            }
+        }
+        Event::Reboot { addr, announcement } => warn!(
+            "[{}] Node rebooted with instances: {:?}, rescanning",
+            addr, announcement.instances
+        ),
+        Event::Property {
+            addr,
+            eoj,
+            epc,
+            value,
+        } => info!("[{}] {:?} {:?} {}", addr, eoj, epc, value),
+        Event::MeterHistory { addr, eoj, sample } => match sample.value {
+            Some(v) => info!(
+                "[{}] {:?} {} {} {}",
+                addr,
+                eoj,
+                sample.timestamp,
+                v,
+                sample.unit.unwrap_or_default()
+            ),
+            None => info!("[{}] {:?} {} not collected", addr, eoj, sample.timestamp),
+        },
+        Event::Household(power) => info!(
+            "Household power: grid {} W, generation {} W, storage {} W, consumption {} W",
+            power.grid.map_or("-".to_string(), |w| w.to_string()),
+            power.generation,
+            power.storage,
+            power.consumption.map_or("-".to_string(), |w| w.to_string()),
+        ),
+        Event::Clock {
+            addr,
+            eoj,
+            datetime,
+            host,
+            drift,
+        } => info!(
+            "[{}] {:?} Clock: {} (host: {}, drift: {}s)",
+            addr, eoj, datetime, host, drift
+        ),
+        Event::Restore {
+            addr,
+            eoj,
+            epc,
+            result,
+        } => match result {
+            RestoreResult::Restored => info!("[{}] {:?} {:?} restored", addr, eoj, epc),
+            RestoreResult::Refused => warn!("[{}] {:?} {:?} refused", addr, eoj, epc),
+            RestoreResult::Mismatch => {
+                warn!("[{}] {:?} {:?} reads back another value", addr, eoj, epc)
+            }
+            RestoreResult::Failed => warn!("[{}] {:?} {:?} failed", addr, eoj, epc),
+        },
+        Event::Progress(progress) => info!("Progress: {}", progress),
+        Event::Timeout {
+            addr,
+            eoj,
+            diagnosis,
+        } => warn!("[{}] {:?} timed out: {}", addr, eoj, diagnosis),
+        Event::Muted {
+            addr,
+            rate,
+            burst,
+            duration,
+        } => warn!(
+            "[{}] Exceeded {} packets/s (burst: {}), ignoring it for {}s",
+            addr, rate, burst, duration
+        ),
+        Event::Unmuted { addr, dropped } => info!(
+            "[{}] Stopped ignoring the source, {} packets were dropped",
+            addr, dropped
+        ),
+        Event::Heartbeat {
+            uptime,
+            packets_sent,
+            packets_received,
+            sources,
+            dropped_records,
+        } => info!(
+            "Heartbeat: up {}s, {} packets sent, {} received from {} sources, {} records dropped",
+            uptime, packets_sent, packets_received, sources, dropped_records
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decoder, packet::EDT};
+
+    #[test]
+    fn test_serialize_property_event() {
+        let eoj = EOJ::new(0x0130, 1);
+        let edt = EDT::from(vec![0x1A]);
+        let event = Event::Property {
+            addr: "192.168.1.10".parse().unwrap(),
+            eoj,
+            epc: ElU8(0xBB),
+            value: decoder::decode(eoj.class(), ElU8(0xBB), &edt, &decoder::Props::new()),
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"property","addr":"192.168.1.10","eoj":"0130:01","epc":"BB","name":"Measured value of room temperature","value":26.0,"unit":"°C","scale":1.0,"raw":"1A"}"#
+        );
+    }
+
+    #[test]
+    fn test_stamped() {
+        let event = Event::Heartbeat {
+            uptime: 60,
+            packets_sent: 10,
+            packets_received: 12,
+            sources: 2,
+            dropped_records: 0,
+        };
+        let stamped = Stamped {
+            gateway: Some("gw-1"),
+            event: &event,
+        };
+        assert_eq!(
+            serde_json::to_string(&stamped).unwrap(),
+            r#"{"gateway":"gw-1","event":"heartbeat","uptime":60,"packets_sent":10,"packets_received":12,"sources":2,"dropped_records":0}"#
+        );
+        let stamped = Stamped {
+            gateway: None,
+            event: &event,
+        };
+        assert_eq!(
+            serde_json::to_string(&stamped).unwrap(),
+            serde_json::to_string(&event).unwrap()
+        );
+        assert!(parse_gateway_id("site-3_gw").is_ok());
+        assert!(parse_gateway_id("a.b").is_err());
+        assert!(parse_gateway_id("").is_err());
+    }
+
+    #[cfg(all(feature = "cbor", feature = "msgpack"))]
+    #[test]
+    fn test_encode_binary_formats() {
+        let eoj = EOJ::new(0x0130, 1);
+        let edt = EDT::from(vec![0x1A]);
+        let event = Event::Property {
+            addr: "192.168.1.10".parse().unwrap(),
+            eoj,
+            epc: ElU8(0xBB),
+            value: decoder::decode(eoj.class(), ElU8(0xBB), &edt, &decoder::Props::new()),
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        // both decode into the same document as the JSON output
+        let cbor = Output::new(Format::Cbor, 1).encode(&event).unwrap();
+        let decoded: serde_json::Value = ciborium::from_reader(&cbor[..]).unwrap();
+        assert_eq!(decoded, json);
+        let msgpack = Output::new(Format::Msgpack, 1).encode(&event).unwrap();
+        let decoded: serde_json::Value = rmp_serde::from_slice(&msgpack).unwrap();
+        assert_eq!(decoded, json);
+        assert!(cbor.len() < serde_json::to_vec(&event).unwrap().len());
+    }
+
+    #[test]
+    fn test_csv_row() {
+        let eoj = EOJ::new(0x0130, 1);
+        let timestamp: Timestamp = "2024-05-01T12:00:00Z".parse().unwrap();
+        let addr = "192.168.1.10".parse().unwrap();
+        let edt = EDT::from(vec![0x1A]);
+        let value = decoder::decode(eoj.class(), ElU8(0xBB), &edt, &decoder::Props::new());
+        assert_eq!(
+            csv_row(timestamp, addr, eoj, ElU8(0xBB), &value),
+            "2024-05-01T12:00:00Z,192.168.1.10,0130:01,BB,Measured value of room temperature,26,°C,1A"
+        );
+        // enumerations are written as their labels
+        let value = decoder::decode(
+            eoj.class(),
+            ElU8(0x80),
+            &EDT::from(vec![0x30]),
+            &decoder::Props::new(),
+        );
+        assert!(csv_row(timestamp, addr, eoj, ElU8(0x80), &value).contains(",Operation status,on,"));
+        // fields containing separators or quotes are quoted
+        let value = PropertyValue {
+            name: Some("a \"quoted\", name"),
+            value: None,
+            unit: None,
+            scale: None,
+            raw: EDT::from(vec![]),
+        };
+        assert_eq!(
+            csv_row(timestamp, addr, eoj, ElU8(0xF0), &value),
+            r#"2024-05-01T12:00:00Z,192.168.1.10,0130:01,F0,"a ""quoted"", name",,,"#
+        );
+    }
+}
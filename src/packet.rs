@@ -1,16 +1,49 @@
+use crate::{
+    consts::{eoj, epc},
+    decoder,
+};
 use bytes::Buf;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use smallvec::SmallVec;
 use std::{
     fmt,
     io::{Cursor, Read},
+    str::FromStr,
 };
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ElU8(pub u8);
 impl fmt::Debug for ElU8 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:02X}", self.0)
     }
 }
+impl Serialize for ElU8 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:?}", self))
+    }
+}
+impl<'de> Deserialize<'de> for ElU8 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        match EDT::from_hex(&s).map_err(de::Error::custom)?.0[..] {
+            [b] => Ok(b),
+            _ => Err(de::Error::custom("invalid byte")),
+        }
+    }
+}
+impl FromStr for ElU8 {
+    type Err = anyhow::Error;
+
+    // parses a hexadecimal byte with or without the "0x" prefix, such as "80" or "0x80"
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let hex = s.strip_prefix("0x").unwrap_or(s);
+        if hex.len() != 2 {
+            anyhow::bail!("invalid byte");
+        }
+        Ok(EDT::from_hex(hex)?.0[0])
+    }
+}
 impl From<ElU8> for usize {
     fn from(value: ElU8) -> Self {
         value.0.into()
@@ -32,10 +65,78 @@ impl From<ElU16> for usize {
 
 const EHD1: u8 = 0x10;
 const EHD2: u8 = 0x81;
+const EHD2_ARBITRARY: u8 = 0x82;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
 pub struct EOJ([ElU8; 3]);
 
+impl EOJ {
+    pub const fn new(class: u16, instance: u8) -> Self {
+        let [group, class] = class.to_be_bytes();
+        Self([ElU8(group), ElU8(class), ElU8(instance)])
+    }
+
+    // class group code and class code
+    pub fn class(&self) -> u16 {
+        u16::from_be_bytes([self.0[0].0, self.0[1].0])
+    }
+
+    pub fn instance(&self) -> u8 {
+        self.0[2].0
+    }
+}
+
+impl fmt::Display for EOJ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04X}:{:02X}", self.class(), self.instance())
+    }
+}
+impl fmt::Debug for EOJ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+impl Serialize for EOJ {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for EOJ {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl FromStr for EOJ {
+    type Err = anyhow::Error;
+
+    // parses "013001", the compact notation "0130:01", or a class name such as "aircon:1"
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (class, instance) = match s.split_once(':') {
+            Some((class, instance)) => (class, instance),
+            None if s.len() == 6 && s.is_ascii() => s.split_at(4),
+            None => anyhow::bail!("invalid EOJ"),
+        };
+        let class = match decoder::class_by_name(class) {
+            Some(class) => class,
+            None if class.len() == 4 && class.chars().all(|c| c.is_ascii_hexdigit()) => {
+                u16::from_str_radix(class, 16)?
+            }
+            None => anyhow::bail!("unknown class {:?}", class),
+        };
+        if instance.is_empty()
+            || instance.len() > 2
+            || !instance.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            anyhow::bail!("invalid instance code");
+        }
+        Ok(Self::new(class, u8::from_str_radix(instance, 16)?))
+    }
+}
+
 impl TryFrom<Vec<ElU8>> for EOJ {
     type Error = anyhow::Error;
 
@@ -62,30 +163,30 @@ impl Packet {
     pub fn new_discovery_request() -> Self {
         Self {
             tid: ElU16(0x0001),
-            seoj: EOJ([ElU8(0x05), ElU8(0xff), ElU8(0x01)]),
-            deoj: EOJ([ElU8(0x0e), ElU8(0xf0), ElU8(0x01)]),
+            seoj: eoj::CONTROLLER,
+            deoj: eoj::NODE_PROFILE,
             esv: ESV::Get,
             opc: ElU8(0x04),
             props: vec![
                 Prop {
-                    epc: ElU8(0x82),
+                    epc: epc::STANDARD_VERSION_INFORMATION,
                     pdc: ElU8(0x00),
-                    edt: EDT(vec![]),
+                    edt: EDT::default(),
                 },
                 Prop {
-                    epc: ElU8(0x83),
+                    epc: epc::IDENTIFICATION_NUMBER,
                     pdc: ElU8(0x00),
-                    edt: EDT(vec![]),
+                    edt: EDT::default(),
                 },
                 Prop {
-                    epc: ElU8(0x8a),
+                    epc: epc::MANUFACTURER_CODE,
                     pdc: ElU8(0x00),
-                    edt: EDT(vec![]),
+                    edt: EDT::default(),
                 },
                 Prop {
-                    epc: ElU8(0xd6),
+                    epc: epc::INSTANCE_LIST_S,
                     pdc: ElU8(0x00),
-                    edt: EDT(vec![]),
+                    edt: EDT::default(),
                 },
             ],
         }
@@ -94,40 +195,102 @@ impl Packet {
     pub fn new_sync_request(deoj: EOJ) -> Self {
         Self {
             tid: ElU16(0x0001),
-            seoj: EOJ([ElU8(0x05), ElU8(0xff), ElU8(0x01)]),
+            seoj: eoj::CONTROLLER,
             deoj,
             esv: ESV::Get,
             opc: ElU8(0x05),
             props: vec![
                 Prop {
-                    epc: ElU8(0x82),
+                    epc: epc::STANDARD_VERSION_INFORMATION,
                     pdc: ElU8(0x00),
-                    edt: EDT(vec![]),
+                    edt: EDT::default(),
                 },
                 Prop {
-                    epc: ElU8(0x83),
+                    epc: epc::IDENTIFICATION_NUMBER,
                     pdc: ElU8(0x00),
-                    edt: EDT(vec![]),
+                    edt: EDT::default(),
                 },
                 Prop {
-                    epc: ElU8(0x9d),
+                    epc: epc::ANNO_PROPERTY_MAP,
                     pdc: ElU8(0x00),
-                    edt: EDT(vec![]),
+                    edt: EDT::default(),
                 },
                 Prop {
-                    epc: ElU8(0x9e),
+                    epc: epc::SET_PROPERTY_MAP,
                     pdc: ElU8(0x00),
-                    edt: EDT(vec![]),
+                    edt: EDT::default(),
                 },
                 Prop {
-                    epc: ElU8(0x9f),
+                    epc: epc::GET_PROPERTY_MAP,
                     pdc: ElU8(0x00),
-                    edt: EDT(vec![]),
+                    edt: EDT::default(),
                 },
             ],
         }
     }
 
+    // fails on more properties than OPC can count, rather than sending a malformed frame
+    pub fn new_get_request(deoj: EOJ, epcs: &[ElU8]) -> anyhow::Result<Self> {
+        let Ok(opc) = u8::try_from(epcs.len()) else {
+            anyhow::bail!("too many properties ({}) in a request", epcs.len());
+        };
+        Ok(Self {
+            tid: ElU16(0x0001),
+            seoj: eoj::CONTROLLER,
+            deoj,
+            esv: ESV::Get,
+            opc: ElU8(opc),
+            props: epcs
+                .iter()
+                .map(|&epc| Prop {
+                    epc,
+                    pdc: ElU8(0x00),
+                    edt: EDT::default(),
+                })
+                .collect(),
+        })
+    }
+
+    // and on EDTs longer than PDC can count
+    pub fn new_set_request(deoj: EOJ, props: Vec<(ElU8, EDT)>) -> anyhow::Result<Self> {
+        let Ok(opc) = u8::try_from(props.len()) else {
+            anyhow::bail!("too many properties ({}) in a request", props.len());
+        };
+        let props = props
+            .into_iter()
+            .map(|(epc, edt)| {
+                let Ok(pdc) = u8::try_from(edt.0.len()) else {
+                    anyhow::bail!("EDT of {:?} is {} bytes long", epc, edt.0.len());
+                };
+                Ok(Prop {
+                    epc,
+                    pdc: ElU8(pdc),
+                    edt,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            tid: ElU16(0x0001),
+            seoj: eoj::CONTROLLER,
+            deoj,
+            esv: ESV::SetC,
+            opc: ElU8(opc),
+            props,
+        })
+    }
+
+    // the device only responds when it refuses some of the properties
+    pub fn new_set_no_response_request(deoj: EOJ, props: Vec<(ElU8, EDT)>) -> anyhow::Result<Self> {
+        Ok(Self {
+            esv: ESV::SetI,
+            ..Self::new_set_request(deoj, props)?
+        })
+    }
+
+    pub fn is_write(&self) -> bool {
+        matches!(self.esv, ESV::SetI | ESV::SetC | ESV::SetGet)
+    }
+
     pub fn is_to(&self, eoj: &EOJ) -> bool {
         self.deoj == *eoj
     }
@@ -137,17 +300,39 @@ impl Packet {
     }
 
     pub fn is_normal_response(&self) -> bool {
-        match self.esv {
-            ESV::SetRes | ESV::GetRes | ESV::SetGetRes => true,
-            _ => false,
-        }
+        matches!(self.esv, ESV::SetRes | ESV::GetRes | ESV::SetGetRes)
+    }
+
+    pub fn is_error_response(&self) -> bool {
+        matches!(
+            self.esv,
+            ESV::SetISNA | ESV::SetCSNA | ESV::GetSNA | ESV::InfSNA | ESV::SetGetSNA
+        )
     }
 
     pub fn get_prop(&self, epc: ElU8) -> Option<&Prop> {
         self.props.iter().find(|prop| prop.epc == epc)
     }
 
-    pub fn to_bytes(self) -> Vec<u8> {
+    // some devices repeat an EPC in a frame, e.g. to carry segmented vendor data
+    pub fn get_props(&self, epc: ElU8) -> impl Iterator<Item = &Prop> {
+        self.props.iter().filter(move |prop| prop.epc == epc)
+    }
+
+    // the properties with data, the EDTs of a repeated EPC being joined in order
+    pub fn to_props(&self) -> decoder::Props {
+        let mut props = decoder::Props::new();
+        for prop in self.props.iter().filter(|p| !p.edt.0.is_empty()) {
+            props
+                .entry(prop.epc)
+                .or_default()
+                .0
+                .extend_from_slice(&prop.edt.0);
+        }
+        props
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
         let mut buf = vec![];
         buf.push(EHD1);
         buf.push(EHD2);
@@ -156,23 +341,62 @@ impl Packet {
         buf.extend_from_slice(&[self.deoj.0[0].0, self.deoj.0[1].0, self.deoj.0[2].0]);
         buf.push(self.esv as u8);
         buf.push(self.opc.0);
-        for prop in self.props {
+        for prop in &self.props {
             buf.push(prop.epc.0);
             buf.push(prop.pdc.0);
-            buf.extend_from_slice(
-                prop.edt
-                    .0
-                    .iter()
-                    .map(|x| x.0)
-                    .collect::<Vec<_>>()
-                    .as_slice(),
-            );
+            buf.extend(prop.edt.0.iter().map(|x| x.0));
         }
         buf
     }
 }
 
-#[derive(Debug, PartialEq)]
+// renders the ESV and the properties by their names and decoded values, in one line,
+// or one property per line with the alternate flag ("{:#}")
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // requests carry the properties of the destination object, and the others those of the source
+        let owner = match self.esv as u8 {
+            0x60..=0x6F => self.deoj,
+            _ => self.seoj,
+        };
+        let props = self.to_props();
+        let class_name = |eoj: EOJ| decoder::class_name(eoj.class()).unwrap_or("unknown");
+        if f.alternate() {
+            writeln!(f, "TID:  {:04X}", self.tid.0)?;
+            writeln!(f, "SEOJ: {} ({})", self.seoj, class_name(self.seoj))?;
+            writeln!(f, "DEOJ: {} ({})", self.deoj, class_name(self.deoj))?;
+            writeln!(f, "ESV:  {:?} ({:02X})", self.esv, self.esv as u8)?;
+            write!(f, "OPC:  {}", self.opc.0)?;
+        } else {
+            write!(
+                f,
+                "{:?} {} -> {} (TID: {:04X})",
+                self.esv, self.seoj, self.deoj, self.tid.0
+            )?;
+        }
+        for (i, p) in self.props.iter().enumerate() {
+            let sep = match (f.alternate(), i) {
+                (true, _) => "\n  ",
+                (false, 0) => ": ",
+                (false, _) => ", ",
+            };
+            write!(f, "{}{:?} ", sep, p.epc)?;
+            if p.edt.0.is_empty() {
+                let name = decoder::lookup(owner.class(), p.epc).map(|d| d.localized_name());
+                write!(f, "{}: (no data)", name.unwrap_or("Unknown property"))?;
+            } else {
+                let value = decoder::decode(owner.class(), p.epc, &p.edt, &props);
+                write!(f, "{}", value)?;
+                if value.value.is_some() {
+                    write!(f, " (0x{})", p.edt.to_hex())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum ESV {
     SetISNA = 0x50,
     SetCSNA = 0x51,
@@ -200,12 +424,70 @@ pub struct Prop {
     pub edt: EDT,  // Property value data (Specified by PDC)
 }
 
-#[derive(Debug, PartialEq)]
-pub struct EDT(pub Vec<ElU8>);
+// most property values are up to 16 bytes, which are stored inline rather than allocated
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct EDT(pub SmallVec<[ElU8; 16]>);
+
+impl EDT {
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{:?}", b)).collect()
+    }
+
+    pub fn from_hex(s: &str) -> anyhow::Result<Self> {
+        if !s.len().is_multiple_of(2) || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("invalid hexadecimal string");
+        }
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from(bytes))
+    }
+}
+
+impl Serialize for EDT {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for EDT {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::from_hex(&String::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
 
 impl From<Vec<u8>> for EDT {
     fn from(value: Vec<u8>) -> Self {
-        Self(value.into_iter().map(ElU8).collect())
+        Self::from(&value[..])
+    }
+}
+
+impl From<&[u8]> for EDT {
+    fn from(value: &[u8]) -> Self {
+        Self(value.iter().copied().map(ElU8).collect())
+    }
+}
+
+// an ECHONET Lite frame, of which only the specified message format is interpreted
+#[derive(Debug)]
+pub enum Frame {
+    Specified(Packet),
+    // EHD2 0x82, whose payload after the TID is defined by each application
+    Arbitrary { tid: ElU16, payload: Vec<u8> },
+}
+
+impl TryFrom<&[u8]> for Frame {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &[u8]) -> anyhow::Result<Self> {
+        match value {
+            [EHD1, EHD2_ARBITRARY, t1, t2, payload @ ..] => Ok(Self::Arbitrary {
+                tid: ElU16(u16::from_be_bytes([*t1, *t2])),
+                payload: payload.to_vec(),
+            }),
+            _ => Ok(Self::Specified(Packet::try_from(value)?)),
+        }
     }
 }
 
@@ -261,14 +543,12 @@ impl TryFrom<&[u8]> for Packet {
             if cursor.remaining() < _pdc.into() {
                 anyhow::bail!("invalid property data");
             }
-            let mut _edt = Vec::with_capacity(_pdc.into());
-            for _ in 0.._pdc {
-                _edt.push(ElU8(cursor.get_u8()));
-            }
+            let edt = EDT::from(&cursor.chunk()[.._pdc.into()]);
+            cursor.advance(_pdc.into());
             let prop = Prop {
                 epc,
                 pdc: ElU8(_pdc),
-                edt: EDT(_edt),
+                edt,
             };
             props.push(prop);
         }
@@ -312,6 +592,147 @@ impl TryFrom<u8> for ESV {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use smallvec::smallvec;
+
+    #[test]
+    fn test_el_u8_from_str() {
+        assert_eq!("80".parse::<ElU8>().unwrap(), ElU8(0x80));
+        assert_eq!("0xb0".parse::<ElU8>().unwrap(), ElU8(0xb0));
+        assert!("8".parse::<ElU8>().is_err());
+        assert!("0x800".parse::<ElU8>().is_err());
+    }
+
+    #[test]
+    fn test_edt_from_hex() {
+        assert_eq!(EDT::from_hex("").unwrap(), EDT(smallvec![]));
+        assert_eq!(
+            EDT::from_hex("004aFF").unwrap(),
+            EDT::from(vec![0x00, 0x4a, 0xff])
+        );
+        assert!(EDT::from_hex("004").is_err());
+        assert!(EDT::from_hex("0x04").is_err());
+    }
+
+    #[test]
+    fn test_eoj_from_str() {
+        assert_eq!(
+            "013001".parse::<EOJ>().unwrap(),
+            EOJ([ElU8(0x01), ElU8(0x30), ElU8(0x01)])
+        );
+        assert_eq!(
+            "0ef001".parse::<EOJ>().unwrap(),
+            EOJ([ElU8(0x0e), ElU8(0xf0), ElU8(0x01)])
+        );
+        assert!("0130".parse::<EOJ>().is_err());
+        assert!("01300g".parse::<EOJ>().is_err());
+        assert!("+13001".parse::<EOJ>().is_err());
+
+        assert_eq!("0130:01".parse::<EOJ>().unwrap(), EOJ::new(0x0130, 1));
+        assert_eq!("0288:1".parse::<EOJ>().unwrap(), EOJ::new(0x0288, 1));
+        assert_eq!("aircon:2".parse::<EOJ>().unwrap(), EOJ::new(0x0130, 2));
+        assert_eq!(
+            "smart-meter:01".parse::<EOJ>().unwrap(),
+            EOJ::new(0x0288, 1)
+        );
+        assert!("0130:".parse::<EOJ>().is_err());
+        assert!("0130:100".parse::<EOJ>().is_err());
+        assert!("fridge:1".parse::<EOJ>().is_err());
+        assert!("°C001".parse::<EOJ>().is_err());
+
+        let eoj = EOJ::new(0x0EF0, 1);
+        assert_eq!(eoj.to_string(), "0EF0:01");
+        assert_eq!(eoj.to_string().parse::<EOJ>().unwrap(), eoj);
+    }
+
+    #[test]
+    fn test_display_packet() {
+        let data = [
+            0x10, 0x81, 0x00, 0x01, 0x01, 0x30, 0x01, 0x05, 0xFF, 0x01, 0x72, 0x03, 0x80, 0x01,
+            0x30, 0xBB, 0x01, 0x1A, 0xF0, 0x00,
+        ];
+        let packet = Packet::try_from(&data[..]).unwrap();
+        assert_eq!(
+            packet.to_string(),
+            "GetRes 0130:01 -> 05FF:01 (TID: 0001): 80 Operation status: on (0x30), \
+             BB Measured value of room temperature: 26 °C (0x1A), F0 Unknown property: (no data)"
+        );
+        assert_eq!(
+            format!("{:#}", packet),
+            "TID:  0001\n\
+             SEOJ: 0130:01 (aircon)\n\
+             DEOJ: 05FF:01 (controller)\n\
+             ESV:  GetRes (72)\n\
+             OPC:  3\n  \
+             80 Operation status: on (0x30)\n  \
+             BB Measured value of room temperature: 26 °C (0x1A)\n  \
+             F0 Unknown property: (no data)"
+        );
+
+        // the properties of a request belong to the destination object
+        let packet = Packet::new_set_request(
+            EOJ::new(0x0130, 1),
+            vec![(ElU8(0xB0), EDT::from(vec![0x42]))],
+        )
+        .unwrap();
+        assert!(packet
+            .to_string()
+            .ends_with("B0 Operation mode setting: cooling (0x42)"));
+    }
+
+    #[test]
+    fn test_new_requests() {
+        let aircon = EOJ::new(0x0130, 1);
+        let epcs = vec![ElU8(0x80); 256];
+        assert_eq!(
+            Packet::new_get_request(aircon, &epcs[..255]).unwrap().opc,
+            ElU8(255)
+        );
+        assert!(Packet::new_get_request(aircon, &epcs).is_err());
+        let props = vec![(ElU8(0x80), EDT::from(vec![0x30])); 256];
+        assert!(Packet::new_set_request(aircon, props[..255].to_vec()).is_ok());
+        assert!(Packet::new_set_request(aircon, props).is_err());
+        let edt = EDT::from(vec![0x00; 256]);
+        assert!(Packet::new_set_request(aircon, vec![(ElU8(0x80), edt.clone())]).is_err());
+        assert!(Packet::new_set_no_response_request(aircon, vec![(ElU8(0x80), edt)]).is_err());
+    }
+
+    #[test]
+    fn test_repeated_epcs() {
+        let data = [
+            0x10, 0x81, 0x00, 0x01, 0x01, 0x30, 0x01, 0x05, 0xFF, 0x01, 0x72, 0x04, 0xF0, 0x02,
+            0x01, 0x02, 0x80, 0x01, 0x30, 0xF0, 0x01, 0x03, 0xF1, 0x00,
+        ];
+        let packet = Packet::try_from(&data[..]).unwrap();
+        assert_eq!(packet.get_props(ElU8(0xF0)).count(), 2);
+        assert_eq!(
+            packet.to_props(),
+            decoder::Props::from([
+                (ElU8(0x80), EDT::from(vec![0x30])),
+                (ElU8(0xF0), EDT::from(vec![0x01, 0x02, 0x03])),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_try_from_frame() {
+        let data = [0x10, 0x82, 0x00, 0x2A, 0xDE, 0xAD];
+        match Frame::try_from(&data[..]).unwrap() {
+            Frame::Arbitrary { tid, payload } => {
+                assert_eq!(tid, ElU16(0x002A));
+                assert_eq!(payload, vec![0xDE, 0xAD]);
+            }
+            f => panic!("unexpected frame: {:?}", f),
+        }
+        let data = [
+            0x10, 0x81, 0x00, 0x01, 0x05, 0xFF, 0x01, 0x0E, 0xF0, 0x01, 0x62, 0x01, 0xD6, 0x00,
+        ];
+        assert!(matches!(
+            Frame::try_from(&data[..]).unwrap(),
+            Frame::Specified(p) if p.esv == ESV::Get
+        ));
+        assert!(Frame::try_from(&[0x10, 0x83, 0x00, 0x01][..]).is_err());
+        assert!(Frame::try_from(&[0x10, 0x82, 0x00][..]).is_err());
+    }
 
     #[test]
     fn test_try_from_packet() {
@@ -337,10 +758,10 @@ mod tests {
             assert_eq!(packet.props.len(), 2);
             assert_eq!(packet.props[0].epc, ElU8(0x82));
             assert_eq!(packet.props[0].pdc, ElU8(0x00));
-            assert_eq!(packet.props[0].edt, EDT(vec![]));
+            assert_eq!(packet.props[0].edt, EDT(smallvec![]));
             assert_eq!(packet.props[1].epc, ElU8(0x83));
             assert_eq!(packet.props[1].pdc, ElU8(0x00));
-            assert_eq!(packet.props[1].edt, EDT(vec![]));
+            assert_eq!(packet.props[1].edt, EDT(smallvec![]));
         }
         {
             let data = [
@@ -0,0 +1,122 @@
+use crate::{
+    client::Client,
+    consts::epc,
+    packet::{Packet, EOJ},
+};
+use std::{fmt, net::IpAddr, time::Duration};
+use tokio::time::{self, Instant, MissedTickBehavior};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Summary {
+    pub sent: u32,
+    pub rtts: Vec<Duration>,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let received = self.rtts.len() as u32;
+        let loss = match self.sent {
+            0 => 0.0,
+            sent => f64::from(sent - received) * 100.0 / f64::from(sent),
+        };
+        write!(
+            f,
+            "{} requests sent, {} responses received, {:.1}% loss",
+            self.sent, received, loss
+        )?;
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        if let (Some(min), Some(max)) = (self.rtts.iter().min(), self.rtts.iter().max()) {
+            let avg = self.rtts.iter().sum::<Duration>() / received;
+            write!(
+                f,
+                "\nrtt min/avg/max = {:.3}/{:.3}/{:.3} ms",
+                ms(*min),
+                ms(avg),
+                ms(*max)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// reads the operation status of the object repeatedly like ICMP ping, until the count is reached or
+// interrupted, failing if the object never answered
+pub async fn run(
+    client: &Client,
+    addr: IpAddr,
+    eoj: EOJ,
+    count: Option<u32>,
+    interval: Duration,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    println!("PING {} {}", addr, eoj);
+    let mut summary = Summary::default();
+    let mut ticker = time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let pinging = async {
+        for seq in 1.. {
+            if count.is_some_and(|c| seq > c) {
+                break;
+            }
+            ticker.tick().await;
+            summary.sent += 1;
+            let started = Instant::now();
+            let packet = Packet::new_get_request(eoj, &[epc::OPERATION_STATUS]).unwrap();
+            match client.request_within(addr, packet, timeout).await {
+                Ok(res) => {
+                    let rtt = started.elapsed();
+                    // a Get_SNA still tells the stack is alive
+                    println!(
+                        "Response from {} {}: seq={} tid={:04X} esv={:?} time={:.3} ms",
+                        addr,
+                        res.seoj,
+                        seq,
+                        res.tid.0,
+                        res.esv,
+                        rtt.as_secs_f64() * 1000.0
+                    );
+                    summary.rtts.push(rtt);
+                }
+                Err(e) => println!("No response for seq={}: {:#}", seq, e),
+            }
+        }
+    };
+    tokio::select! {
+        _ = pinging => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    println!("--- {} {} ping statistics ---\n{}", addr, eoj, summary);
+    if summary.rtts.is_empty() {
+        anyhow::bail!("no response from {} {}", addr, eoj);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary {
+            sent: 4,
+            rtts: vec![
+                Duration::from_millis(10),
+                Duration::from_millis(30),
+                Duration::from_millis(20),
+            ],
+        };
+        assert_eq!(
+            summary.to_string(),
+            "4 requests sent, 3 responses received, 25.0% loss\nrtt min/avg/max = 10.000/20.000/30.000 ms"
+        );
+        let summary = Summary {
+            sent: 2,
+            rtts: vec![],
+        };
+        assert_eq!(
+            summary.to_string(),
+            "2 requests sent, 0 responses received, 100.0% loss"
+        );
+    }
+}
@@ -0,0 +1,447 @@
+use crate::{
+    client::Client,
+    consts::{eoj, epc},
+    decoder::{self, PropertyValue},
+    packet::{ElU8, Packet, EOJ},
+    response::SyncResponse,
+    scan,
+};
+use clap::ValueEnum;
+use jiff::Timestamp;
+use log::{info, warn};
+use std::{
+    fmt::Write,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{sync::mpsc, task::JoinSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Markdown,
+    // a standalone page without external resources
+    Html,
+}
+
+pub struct Report {
+    pub generated: Timestamp,
+    pub nodes: Vec<Node>,
+    pub unreachable: Vec<Unreachable>,
+}
+
+pub struct Node {
+    pub addr: IpAddr,
+    // the node profile, if it could be read, followed by the device objects
+    pub objects: Vec<Object>,
+}
+
+pub struct Object {
+    pub eoj: EOJ,
+    pub props: Vec<(ElU8, PropertyValue)>,
+}
+
+pub struct Unreachable {
+    pub addr: IpAddr,
+    // the whole node did not answer if none
+    pub eoj: Option<EOJ>,
+    pub reason: String,
+}
+
+// discovers the nodes answering within the wait, plus the given hosts, and reads all the properties of them
+pub async fn collect(
+    client: Arc<Client>,
+    rx: mpsc::Receiver<(Vec<u8>, SocketAddr)>,
+    wait: Duration,
+    hosts: &[IpAddr],
+) -> anyhow::Result<Report> {
+    let mut nodes = scan::discover(Arc::clone(&client), rx, wait).await?;
+    info!("Discovered {} nodes", nodes.len());
+
+    let mut unreachable = vec![];
+    // the hosts missing the multicast are asked by unicast
+    for &addr in hosts {
+        if nodes.contains_key(&addr) {
+            continue;
+        }
+        match scan::instances(&client, addr).await {
+            Ok(instances) => {
+                nodes.insert(addr, instances);
+            }
+            Err(e) => unreachable.push(Unreachable {
+                addr,
+                eoj: None,
+                reason: format!("{:#}", e),
+            }),
+        }
+    }
+
+    let mut tasks = JoinSet::new();
+    for (addr, instances) in nodes {
+        let client = Arc::clone(&client);
+        tasks.spawn(async move {
+            let mut node = Node {
+                addr,
+                objects: vec![],
+            };
+            let mut unreachable = vec![];
+            for eoj in std::iter::once(eoj::NODE_PROFILE).chain(instances) {
+                match walk(&client, addr, eoj).await {
+                    Ok(object) => node.objects.push(object),
+                    Err(e) => {
+                        warn!("[{}] Failed to read {:?}: {:?}", addr, eoj, e);
+                        unreachable.push(Unreachable {
+                            addr,
+                            eoj: Some(eoj),
+                            reason: format!("{:#}", e),
+                        });
+                    }
+                }
+            }
+            (node, unreachable)
+        });
+    }
+    let mut report = Report {
+        generated: Timestamp::now(),
+        nodes: vec![],
+        unreachable,
+    };
+    while let Some(res) = tasks.join_next().await {
+        let (node, unreachable) = res?;
+        if !node.objects.is_empty() {
+            report.nodes.push(node);
+        }
+        report.unreachable.extend(unreachable);
+    }
+    report.nodes.sort_by_key(|n| n.addr);
+    report
+        .unreachable
+        .sort_by_key(|u| (u.addr, u.eoj.map(|e| (e.class(), e.instance()))));
+    Ok(report)
+}
+
+async fn walk(client: &Client, addr: IpAddr, eoj: EOJ) -> anyhow::Result<Object> {
+    let sync = SyncResponse::try_from(&client.request(addr, Packet::new_sync_request(eoj)).await?)?;
+    let props = scan::get_props(client, addr, eoj, &sync.get_props).await;
+    Ok(Object {
+        eoj,
+        props: props
+            .iter()
+            .map(|(&epc, edt)| (epc, decoder::decode(eoj.class(), epc, edt, &props)))
+            .collect(),
+    })
+}
+
+impl Object {
+    fn get(&self, epc: ElU8) -> Option<&PropertyValue> {
+        self.props.iter().find(|(e, _)| *e == epc).map(|(_, v)| v)
+    }
+
+    fn class_name(&self) -> &'static str {
+        decoder::class_name(self.eoj.class()).unwrap_or("unknown")
+    }
+}
+
+impl Node {
+    fn profile(&self) -> Option<&Object> {
+        self.objects
+            .iter()
+            .find(|o| o.eoj.class() == decoder::NODE_PROFILE)
+    }
+
+    // vendor properties of the object, falling back to the ones of the node profile
+    fn vendor<'a>(&'a self, object: &'a Object, epc: ElU8) -> Option<&'a PropertyValue> {
+        object
+            .get(epc)
+            .or_else(|| self.profile().and_then(|o| o.get(epc)))
+            .filter(|v| !v.raw.0.is_empty())
+    }
+}
+
+// the product code is ASCII padded with spaces or NULs
+fn product_code(value: &PropertyValue) -> String {
+    let bytes: Vec<u8> = value.raw.0.iter().map(|b| b.0).collect();
+    match std::str::from_utf8(&bytes) {
+        Ok(s) if s.is_ascii() => s.trim_end_matches(['\0', ' ']).to_string(),
+        _ => value.raw.to_hex(),
+    }
+}
+
+// the node profile has major.minor, while the device objects have the release of the appendix
+fn version(eoj: EOJ, value: &PropertyValue) -> String {
+    let raw = &value.raw.0;
+    match raw.len() {
+        4 if eoj.class() == decoder::NODE_PROFILE => format!("{}.{}", raw[0].0, raw[1].0),
+        4 if raw[2].0.is_ascii_uppercase() => format!("Release {}", raw[2].0 as char),
+        _ => value.raw.to_hex(),
+    }
+}
+
+fn value(value: &PropertyValue) -> String {
+    match (&value.value, value.unit) {
+        (Some(v), Some(unit)) => format!("{} {}", v, unit),
+        (Some(v), None) => v.to_string(),
+        (None, _) if value.raw.0.is_empty() => "(no data)".to_string(),
+        (None, _) => "-".to_string(),
+    }
+}
+
+struct Table {
+    headers: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+impl Report {
+    fn devices(&self) -> Table {
+        let mut rows = vec![];
+        for node in &self.nodes {
+            let devices = node
+                .objects
+                .iter()
+                .filter(|o| o.eoj.class() != decoder::NODE_PROFILE);
+            for object in devices {
+                rows.push(vec![
+                    node.addr.to_string(),
+                    object.eoj.to_string(),
+                    object.class_name().to_string(),
+                    node.vendor(object, epc::MANUFACTURER_CODE)
+                        .map(|v| v.raw.to_hex())
+                        .unwrap_or_default(),
+                    node.vendor(object, epc::PRODUCT_CODE)
+                        .map(product_code)
+                        .unwrap_or_default(),
+                    object
+                        .get(epc::STANDARD_VERSION_INFORMATION)
+                        .map(|v| version(object.eoj, v))
+                        .unwrap_or_default(),
+                    node.profile()
+                        .and_then(|o| o.get(epc::STANDARD_VERSION_INFORMATION))
+                        .map(|v| version(eoj::NODE_PROFILE, v))
+                        .unwrap_or_default(),
+                ]);
+            }
+        }
+        Table {
+            headers: &[
+                "Address",
+                "Object",
+                "Class",
+                "Manufacturer",
+                "Product code",
+                "Version",
+                "Node version",
+            ],
+            rows,
+        }
+    }
+
+    fn properties(object: &Object) -> Table {
+        Table {
+            headers: &["EPC", "Property", "Value", "Raw"],
+            rows: object
+                .props
+                .iter()
+                .map(|(epc, v)| {
+                    vec![
+                        format!("{:?}", epc),
+                        v.name.unwrap_or("Unknown property").to_string(),
+                        value(v),
+                        v.raw.to_hex(),
+                    ]
+                })
+                .collect(),
+        }
+    }
+
+    fn unreachable(&self) -> Table {
+        Table {
+            headers: &["Address", "Object", "Reason"],
+            rows: self
+                .unreachable
+                .iter()
+                .map(|u| {
+                    vec![
+                        u.addr.to_string(),
+                        u.eoj.map_or("(node)".to_string(), |e| e.to_string()),
+                        u.reason.clone(),
+                    ]
+                })
+                .collect(),
+        }
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Markdown => self.to_markdown(),
+            Format::Html => self.to_html(),
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut s = String::new();
+        writeln!(s, "# ECHONET Lite site survey\n").unwrap();
+        writeln!(s, "Generated at {}.\n", self.generated).unwrap();
+        writeln!(s, "## Devices\n").unwrap();
+        markdown_table(&mut s, &self.devices());
+        writeln!(s, "\n## Properties").unwrap();
+        for node in &self.nodes {
+            for object in &node.objects {
+                writeln!(
+                    s,
+                    "\n### {} {} ({})\n",
+                    node.addr,
+                    object.eoj,
+                    object.class_name()
+                )
+                .unwrap();
+                markdown_table(&mut s, &Self::properties(object));
+            }
+        }
+        writeln!(s, "\n## Unreachable devices\n").unwrap();
+        if self.unreachable.is_empty() {
+            writeln!(s, "None.").unwrap();
+        } else {
+            markdown_table(&mut s, &self.unreachable());
+        }
+        s
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut s = String::new();
+        s.push_str(concat!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n",
+            "<title>ECHONET Lite site survey</title>\n<style>\n",
+            "body { font-family: sans-serif; margin: 2em; }\n",
+            "table { border-collapse: collapse; margin-bottom: 1em; }\n",
+            "th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }\n",
+            "th { background: #f0f0f0; }\n",
+            "</style>\n</head>\n<body>\n",
+            "<h1>ECHONET Lite site survey</h1>\n",
+        ));
+        writeln!(s, "<p>Generated at {}.</p>", self.generated).unwrap();
+        writeln!(s, "<h2>Devices</h2>").unwrap();
+        html_table(&mut s, &self.devices());
+        writeln!(s, "<h2>Properties</h2>").unwrap();
+        for node in &self.nodes {
+            for object in &node.objects {
+                writeln!(
+                    s,
+                    "<h3>{} {} ({})</h3>",
+                    node.addr,
+                    object.eoj,
+                    object.class_name()
+                )
+                .unwrap();
+                html_table(&mut s, &Self::properties(object));
+            }
+        }
+        writeln!(s, "<h2>Unreachable devices</h2>").unwrap();
+        if self.unreachable.is_empty() {
+            writeln!(s, "<p>None.</p>").unwrap();
+        } else {
+            html_table(&mut s, &self.unreachable());
+        }
+        s.push_str("</body>\n</html>\n");
+        s
+    }
+}
+
+fn markdown_table(s: &mut String, table: &Table) {
+    let cell = |c: &str| c.replace('|', "\\|").replace('\n', " ");
+    writeln!(s, "| {} |", table.headers.join(" | ")).unwrap();
+    writeln!(s, "|{}", "---|".repeat(table.headers.len())).unwrap();
+    for row in &table.rows {
+        let cells: Vec<String> = row.iter().map(|c| cell(c)).collect();
+        writeln!(s, "| {} |", cells.join(" | ")).unwrap();
+    }
+}
+
+fn html_table(s: &mut String, table: &Table) {
+    s.push_str("<table>\n<tr>");
+    for h in table.headers {
+        write!(s, "<th>{}</th>", escape(h)).unwrap();
+    }
+    s.push_str("</tr>\n");
+    for row in &table.rows {
+        s.push_str("<tr>");
+        for c in row {
+            write!(s, "<td>{}</td>", escape(c)).unwrap();
+        }
+        s.push_str("</tr>\n");
+    }
+    s.push_str("</table>\n");
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decoder::Props, packet::EDT};
+
+    fn object(eoj: EOJ, props: &[(u8, Vec<u8>)]) -> Object {
+        let props: Props = props
+            .iter()
+            .map(|(epc, edt)| (ElU8(*epc), EDT::from(edt.clone())))
+            .collect();
+        Object {
+            eoj,
+            props: props
+                .iter()
+                .map(|(&epc, edt)| (epc, decoder::decode(eoj.class(), epc, edt, &props)))
+                .collect(),
+        }
+    }
+
+    fn report() -> Report {
+        Report {
+            generated: "2024-05-01T12:00:00Z".parse().unwrap(),
+            nodes: vec![Node {
+                addr: "192.168.1.10".parse().unwrap(),
+                objects: vec![
+                    object(
+                        eoj::NODE_PROFILE,
+                        &[
+                            (0x82, vec![0x01, 0x0D, 0x01, 0x00]),
+                            (0x8A, vec![0x00, 0x00, 0x08]),
+                            (0x8C, b"AC-100\0\0\0\0\0\0".to_vec()),
+                        ],
+                    ),
+                    object(
+                        EOJ::new(0x0130, 1),
+                        &[(0x80, vec![0x30]), (0x82, vec![0x00, 0x00, 0x52, 0x01])],
+                    ),
+                ],
+            }],
+            unreachable: vec![Unreachable {
+                addr: "192.168.1.11".parse().unwrap(),
+                eoj: None,
+                reason: "timed out <3s>".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_markdown() {
+        let md = report().to_markdown();
+        assert!(md.contains(
+            "| 192.168.1.10 | 0130:01 | aircon | 000008 | AC-100 | Release R | 1.13 |\n"
+        ));
+        assert!(md.contains("### 192.168.1.10 0130:01 (aircon)\n"));
+        assert!(md.contains("| 80 | Operation status | on | 30 |\n"));
+        assert!(md.contains("| 192.168.1.11 | (node) | timed out <3s> |\n"));
+    }
+
+    #[test]
+    fn test_html() {
+        let html = report().to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<td>timed out &lt;3s&gt;</td>"));
+        assert!(html.ends_with("</html>\n"));
+    }
+}
@@ -1,7 +1,12 @@
-use crate::packet::{ElU8, Packet, EDT, EOJ};
+use crate::{
+    consts::{eoj, epc},
+    decoder,
+    packet::{ElU8, Packet, EDT, EOJ, ESV},
+};
+use serde::{Serialize, Serializer};
+use std::fmt;
 
-#[allow(dead_code)]
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct DiscoveryResponse {
     pub eoj: EOJ,
     pub instances: Vec<EOJ>,
@@ -14,35 +19,330 @@ impl TryFrom<&Packet> for DiscoveryResponse {
         if !p.is_normal_response() {
             anyhow::bail!("not a response");
         }
-        let controller = EOJ::try_from(vec![ElU8(0x05), ElU8(0xFF), ElU8(0x01)]).unwrap();
-        if !p.is_to(&controller) {
+        if !p.is_to(&eoj::CONTROLLER) {
             anyhow::bail!("invalid DEOJ");
         }
-        let node_profile = EOJ::try_from(vec![ElU8(0x0E), ElU8(0xF0), ElU8(0x01)]).unwrap();
-        if !p.is_from(&node_profile) {
+        if !p.is_from(&eoj::NODE_PROFILE) {
             anyhow::bail!("invalid SEOJ");
         }
-        let Some(prop) = p.get_prop(ElU8(0xD6)) else {
+        let lists = p
+            .get_props(epc::INSTANCE_LIST_S)
+            .map(|prop| parse_instance_list(&prop.edt))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if lists.is_empty() {
             anyhow::bail!("not found instance list property");
+        }
+        Ok(Self {
+            eoj: p.seoj,
+            instances: join_lists(lists).1,
+        })
+    }
+}
+
+// the announcement a node makes when it boots, by the instance list notification and the operation
+// status of the node profile turning on, after which its property maps may have changed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BootAnnouncement {
+    pub eoj: EOJ,
+    // empty when only the operation status is announced
+    pub instances: Vec<EOJ>,
+}
+
+// the operation status of the node profile while the node is running
+const BOOTED: ElU8 = ElU8(0x30);
+
+impl TryFrom<&Packet> for BootAnnouncement {
+    type Error = anyhow::Error;
+
+    fn try_from(p: &Packet) -> anyhow::Result<Self> {
+        if !matches!(p.esv, ESV::Inf | ESV::InfC) {
+            anyhow::bail!("not a notification");
+        }
+        // including the send-only node profiles
+        if p.seoj.class() != decoder::NODE_PROFILE {
+            anyhow::bail!("invalid SEOJ");
+        }
+        let lists = p
+            .get_props(epc::INSTANCE_LIST_NOTIFICATION)
+            .map(|prop| parse_instance_list(&prop.edt))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let booted = p
+            .get_prop(epc::OPERATION_STATUS)
+            .is_some_and(|prop| prop.edt.0[..] == [BOOTED]);
+        if lists.is_empty() && !booted {
+            anyhow::bail!("not a boot announcement");
+        }
+        Ok(Self {
+            eoj: p.seoj,
+            instances: join_lists(lists).1,
+        })
+    }
+}
+
+// the lists hold up to these many entries, while their first bytes count all of them
+const MAX_LISTED_INSTANCES: usize = 84;
+const MAX_LISTED_CLASSES: usize = 8;
+
+// returns the number of instances in the first byte and the instances in the 3-byte chunks of the rest
+fn parse_instance_list(edt: &EDT) -> anyhow::Result<(usize, Vec<EOJ>)> {
+    let Some((count, rest)) = edt.0.split_first() else {
+        anyhow::bail!("empty instance list");
+    };
+    let instances = rest
+        .chunks(3)
+        .map(|chunk| EOJ::try_from(chunk.to_vec()))
+        .collect::<anyhow::Result<_>>()?;
+    Ok((count.0.into(), instances))
+}
+
+// returns the number of classes in the first byte and the classes in the 2-byte chunks of the rest
+fn parse_class_list(edt: &EDT) -> anyhow::Result<(usize, Vec<ClassCode>)> {
+    let Some((count, rest)) = edt.0.split_first() else {
+        anyhow::bail!("empty class list");
+    };
+    let classes = rest
+        .chunks(2)
+        .map(|chunk| match chunk {
+            [group, class] => Ok(ClassCode(u16::from_be_bytes([group.0, class.0]))),
+            _ => anyhow::bail!("invalid class list"),
+        })
+        .collect::<anyhow::Result<_>>()?;
+    Ok((count.0.into(), classes))
+}
+
+// a repeated list is regarded as a continuation, adding up its count
+fn join_lists<T>(lists: Vec<(usize, Vec<T>)>) -> (usize, Vec<T>) {
+    lists
+        .into_iter()
+        .fold((0, vec![]), |(count, mut all), (n, entries)| {
+            all.extend(entries);
+            (count + n, all)
+        })
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ClassCode(pub u16);
+
+impl fmt::Debug for ClassCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04X}", self.0)
+    }
+}
+
+impl Serialize for ClassCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&format_args!("{:?}", self))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Inconsistency {
+    // the number a property declares differs from the number of the listed entries
+    Count {
+        epc: ElU8,
+        declared: usize,
+        listed: usize,
+    },
+    // a class appears in only one of the instance list and the class list
+    Class {
+        class: ClassCode,
+        only_in: ElU8,
+    },
+}
+
+// the self-node properties of a node profile, checked against each other
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NodeProfile {
+    pub eoj: EOJ,
+    pub instances: Vec<EOJ>,
+    pub classes: Vec<ClassCode>,
+    pub inconsistencies: Vec<Inconsistency>,
+}
+
+impl TryFrom<&Packet> for NodeProfile {
+    type Error = anyhow::Error;
+
+    // the properties other than the instance list are optional, as some nodes answer Get_SNA for them
+    fn try_from(p: &Packet) -> anyhow::Result<Self> {
+        if !p.is_normal_response() && !p.is_error_response() {
+            anyhow::bail!("not a response");
+        }
+        if !p.is_from(&eoj::NODE_PROFILE) {
+            anyhow::bail!("invalid SEOJ");
+        }
+        let edts = |epc| p.get_props(epc).map(|p| &p.edt).filter(|e| !e.0.is_empty());
+        let instance_lists = edts(epc::INSTANCE_LIST_S)
+            .map(parse_instance_list)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if instance_lists.is_empty() {
+            anyhow::bail!("not found instance list property");
+        }
+        let (instance_count, instances) = join_lists(instance_lists);
+        let class_lists = edts(epc::CLASS_LIST_S)
+            .map(parse_class_list)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let class_list = (!class_lists.is_empty()).then(|| join_lists(class_lists));
+        let number = |epc| {
+            edts(epc)
+                .next()
+                .map(|e| e.0.iter().fold(0, |acc, b| (acc << 8) | b.0 as usize))
+        };
+
+        let mut inconsistencies = vec![];
+        let mut check = |epc, declared: usize, listed: usize| {
+            if declared != listed {
+                inconsistencies.push(Inconsistency::Count {
+                    epc,
+                    declared,
+                    listed,
+                });
+            }
         };
-        // the first byte shows the number of instances(EOJs) and 3-byte chunks in the rest bytes represent instances
-        let mut instances = Vec::with_capacity(prop.edt.0[0].0.into());
-        for chunk in prop.edt.0[1..].chunks(3) {
-            let eoj = EOJ::try_from(chunk.to_vec())?;
-            instances.push(eoj);
+        check(
+            epc::INSTANCE_LIST_S,
+            instance_count.min(MAX_LISTED_INSTANCES),
+            instances.len(),
+        );
+        // the counts can only be compared with complete lists
+        let all_instances = instances.len() < MAX_LISTED_INSTANCES;
+        let mut instance_classes: Vec<ClassCode> =
+            instances.iter().map(|e| ClassCode(e.class())).collect();
+        instance_classes.sort();
+        instance_classes.dedup();
+        if let Some(n) = number(epc::NUMBER_OF_INSTANCES).filter(|_| all_instances) {
+            check(epc::NUMBER_OF_INSTANCES, n, instances.len());
         }
+        // which counts the node profile class as well
+        if let Some(n) = number(epc::NUMBER_OF_CLASSES).filter(|_| all_instances) {
+            check(epc::NUMBER_OF_CLASSES, n, instance_classes.len() + 1);
+        }
+        let classes = match class_list {
+            Some((class_count, classes)) => {
+                check(
+                    epc::CLASS_LIST_S,
+                    class_count.min(MAX_LISTED_CLASSES),
+                    classes.len(),
+                );
+                if all_instances && classes.len() < MAX_LISTED_CLASSES {
+                    for &class in classes.iter().filter(|c| !instance_classes.contains(c)) {
+                        inconsistencies.push(Inconsistency::Class {
+                            class,
+                            only_in: epc::CLASS_LIST_S,
+                        });
+                    }
+                    for &class in instance_classes.iter().filter(|c| !classes.contains(c)) {
+                        inconsistencies.push(Inconsistency::Class {
+                            class,
+                            only_in: epc::INSTANCE_LIST_S,
+                        });
+                    }
+                }
+                classes
+            }
+            None => vec![],
+        };
         Ok(Self {
-            eoj: p.seoj.clone(),
+            eoj: p.seoj,
             instances,
+            classes,
+            inconsistencies,
         })
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct SVI([ElU8; 4]);
+pub enum Anomaly {
+    Unrequested(ElU8),
+    Missing(ElU8),
+    ImplausibleSize(ElU8, usize),
+}
+
+// drops the properties the device answered without being asked for or in a size they can't be of, so
+// that they are never reported as the requested values, and returns what is wrong with the response
+// to a Get request
+pub fn validate_get_response(request: &Packet, response: &mut Packet) -> Vec<Anomaly> {
+    let requested: Vec<ElU8> = request.props.iter().map(|p| p.epc).collect();
+    let class = response.seoj.class();
+    let mut anomalies = vec![];
+    let mut implausible = vec![];
+    response.props.retain(|p| {
+        if !requested.contains(&p.epc) {
+            anomalies.push(Anomaly::Unrequested(p.epc));
+            return false;
+        }
+        // an empty EDT of Get_SNA only tells the property is not available
+        if !p.edt.0.is_empty() && !decoder::is_plausible_size(class, p.epc, p.edt.0.len()) {
+            implausible.push(Anomaly::ImplausibleSize(p.epc, p.edt.0.len()));
+            return false;
+        }
+        true
+    });
+    response.opc = ElU8(response.props.len() as u8);
+    for epc in requested {
+        let dropped = implausible
+            .iter()
+            .any(|a| matches!(a, Anomaly::ImplausibleSize(e, _) if *e == epc));
+        if !dropped && response.get_prop(epc).is_none() {
+            anomalies.push(Anomaly::Missing(epc));
+        }
+    }
+    anomalies.extend(implausible);
+    anomalies
+}
+
+// a property the device refused to write, whose EDT it echoes in SetC_SNA or SetI_SNA
+#[derive(Debug, Clone, PartialEq)]
+pub enum WriteError {
+    Rejected { epc: ElU8, attempted: EDT },
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rejected { epc, attempted } => {
+                write!(f, "rejected {:?}=0x{}", epc, attempted.to_hex())
+            }
+        }
+    }
+}
+
+impl WriteError {
+    pub fn epc(&self) -> ElU8 {
+        match self {
+            Self::Rejected { epc, .. } => *epc,
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+// the properties refused in the response to a write, as the accepted ones are answered with empty EDTs
+pub fn write_errors(response: &Packet) -> Vec<WriteError> {
+    if response.is_normal_response() {
+        return vec![];
+    }
+    response
+        .props
+        .iter()
+        .filter(|p| p.pdc.0 > 0)
+        .map(|p| WriteError::Rejected {
+            epc: p.epc,
+            attempted: p.edt.clone(),
+        })
+        .collect()
+}
 
-#[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq)]
+pub struct SVI([ElU8; 4]);
+
+impl Serialize for SVI {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&EDT(self.0[..].into()).to_hex())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct SyncResponse {
     pub eoj: EOJ,
     pub svi: SVI, // Standard Version Information
@@ -58,37 +358,56 @@ impl TryFrom<&Packet> for SyncResponse {
         if !p.is_normal_response() {
             anyhow::bail!("not a response");
         }
-        let controller = EOJ::try_from(vec![ElU8(0x05), ElU8(0xFF), ElU8(0x01)]).unwrap();
-        if !p.is_to(&controller) {
+        if !p.is_to(&eoj::CONTROLLER) {
             anyhow::bail!("invalid DEOJ");
         }
-        let Some(svi) = p.get_prop(ElU8(0x82)) else {
+        let Some(svi) = p.get_prop(epc::STANDARD_VERSION_INFORMATION) else {
             anyhow::bail!("not found standard version information");
         };
-        let Some(anno) = p.get_prop(ElU8(0x9D)) else {
+        let [a, b, c, d] = svi.edt.0[..] else {
+            anyhow::bail!("invalid standard version information");
+        };
+        let Some(anno_props) = property_map(p, epc::ANNO_PROPERTY_MAP)? else {
             anyhow::bail!("not found announcement property map");
         };
-        let Some(get) = p.get_prop(ElU8(0x9F)) else {
+        let Some(get_props) = property_map(p, epc::GET_PROPERTY_MAP)? else {
             anyhow::bail!("not found get property map");
         };
-        let Some(set) = p.get_prop(ElU8(0x9E)) else {
+        let Some(set_props) = property_map(p, epc::SET_PROPERTY_MAP)? else {
             anyhow::bail!("not found set property map");
         };
         Ok(Self {
-            eoj: p.seoj.clone(),
-            svi: SVI([svi.edt.0[0], svi.edt.0[1], svi.edt.0[2], svi.edt.0[3]]),
-            anno_props: parse_property_map(&anno.edt),
-            get_props: parse_property_map(&get.edt),
-            set_props: parse_property_map(&set.edt),
+            eoj: p.seoj,
+            svi: SVI([a, b, c, d]),
+            anno_props,
+            get_props,
+            set_props,
         })
     }
 }
 
-fn parse_property_map(edt: &EDT) -> Vec<ElU8> {
+// merges the maps of a repeated EPC
+fn property_map(p: &Packet, epc: ElU8) -> anyhow::Result<Option<Vec<ElU8>>> {
+    let mut epcs: Option<Vec<ElU8>> = None;
+    for prop in p.get_props(epc) {
+        let all = epcs.get_or_insert_with(Vec::new);
+        for epc in parse_property_map(&prop.edt)? {
+            if !all.contains(&epc) {
+                all.push(epc);
+            }
+        }
+    }
+    Ok(epcs)
+}
+
+pub fn parse_property_map(edt: &EDT) -> anyhow::Result<Vec<ElU8>> {
     // the first byte always shows the number of properties
-    if edt.0[0].0 < 16 {
+    let Some((n, rest)) = edt.0.split_first() else {
+        anyhow::bail!("empty property map");
+    };
+    if n.0 < 16 {
         // if the number of properties is less than 16, each of the rest bytes represents a property
-        return edt.0[1..].to_vec();
+        return Ok(rest.to_vec());
     }
     // if the number of properties is more than or equal to 16,
     // the properties are represented by the bits of the rest bytes
@@ -98,26 +417,30 @@ fn parse_property_map(edt: &EDT) -> Vec<ElU8> {
     // |  3rd byte | 0xF1 | 0xE1 | 0xD1 | 0xC1 | 0xB1 | 0xA1 | 0x91 | 0x81 |
     // |       ... |  ... |  ... |  ... |  ... |  ... |  ... |  ... |  ... |
     // | 17th byte | 0xFF | 0xEF | 0xDF | 0xCF | 0xBF | 0xAF | 0x9F | 0x8F |
-    let mut props = Vec::with_capacity(edt.0[0].0.into());
-    for (i, b) in edt.0[1..].iter().enumerate() {
-        for j in 0..(8 * size_of::<u8>()) {
+    if rest.len() > 16 {
+        anyhow::bail!("property map of {} bytes", edt.0.len());
+    }
+    let mut props = Vec::with_capacity(n.0.into());
+    for (i, b) in rest.iter().enumerate() {
+        for j in 0..u8::BITS {
             if b.0 & (1 << j) != 0 {
                 props.push(ElU8((0x80 + 0x10 * j as u8) + i as u8));
             }
         }
     }
-    props
+    Ok(props)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::packet::{ElU16, ElU8, Prop, EDT, ESV};
+    use smallvec::smallvec;
 
     #[test]
     fn test_parse_property_map() {
         {
-            let edt = EDT(vec![
+            let edt = EDT(smallvec![
                 ElU8(0x08),
                 ElU8(0x80),
                 ElU8(0x81),
@@ -129,7 +452,7 @@ mod tests {
                 ElU8(0xb3),
             ]);
             assert_eq!(
-                parse_property_map(&edt),
+                parse_property_map(&edt).unwrap(),
                 vec![
                     ElU8(0x80),
                     ElU8(0x81),
@@ -143,7 +466,7 @@ mod tests {
             );
         }
         {
-            let edt = EDT(vec![
+            let edt = EDT(smallvec![
                 ElU8(0x12),
                 ElU8(0x0d),
                 ElU8(0x01),
@@ -163,7 +486,7 @@ mod tests {
                 ElU8(0x03),
             ]);
             assert_eq!(
-                parse_property_map(&edt),
+                parse_property_map(&edt).unwrap(),
                 vec![
                     ElU8(0x80),
                     ElU8(0xA0),
@@ -186,11 +509,17 @@ mod tests {
                 ]
             );
         }
+        // malformed ones sent by any host on the LAN
+        assert!(parse_property_map(&EDT(smallvec![])).is_err());
+        let mut edt = EDT(smallvec![ElU8(0x12); 17]);
+        assert!(parse_property_map(&edt).is_ok());
+        edt.0.push(ElU8(0xFF));
+        assert!(parse_property_map(&edt).is_err());
     }
 
     #[test]
     fn test_sync_response_try_from() {
-        let packet = Packet {
+        let mut packet = Packet {
             tid: ElU16(0x01),
             seoj: EOJ::try_from(vec![ElU8(0x01), ElU8(0x30), ElU8(0x01)]).unwrap(),
             deoj: EOJ::try_from(vec![ElU8(0x05), ElU8(0xFF), ElU8(0x01)]).unwrap(),
@@ -200,12 +529,12 @@ mod tests {
                 Prop {
                     epc: ElU8(0x82),
                     pdc: ElU8(0x04),
-                    edt: EDT(vec![ElU8(0x00), ElU8(0x00), ElU8(0x52), ElU8(0x00)]),
+                    edt: EDT(smallvec![ElU8(0x00), ElU8(0x00), ElU8(0x52), ElU8(0x00)]),
                 },
                 Prop {
                     epc: ElU8(0x9D),
                     pdc: ElU8(0x07),
-                    edt: EDT(vec![
+                    edt: EDT(smallvec![
                         ElU8(0x06),
                         ElU8(0x80),
                         ElU8(0x81),
@@ -218,7 +547,7 @@ mod tests {
                 Prop {
                     epc: ElU8(0x9E),
                     pdc: ElU8(0x09),
-                    edt: EDT(vec![
+                    edt: EDT(smallvec![
                         ElU8(0x08),
                         ElU8(0x80),
                         ElU8(0x81),
@@ -233,7 +562,7 @@ mod tests {
                 Prop {
                     epc: ElU8(0x9F),
                     pdc: ElU8(0x11),
-                    edt: EDT(vec![
+                    edt: EDT(smallvec![
                         ElU8(0x12),
                         ElU8(0x0D),
                         ElU8(0x01),
@@ -305,6 +634,11 @@ mod tests {
                 ],
             }
         );
+        let svi = std::mem::replace(&mut packet.props[0].edt, EDT(smallvec![ElU8(0x00)]));
+        assert!(SyncResponse::try_from(&packet).is_err());
+        packet.props[0].edt = svi;
+        packet.props[1].edt = EDT(smallvec![]);
+        assert!(SyncResponse::try_from(&packet).is_err());
     }
 
     #[test]
@@ -318,7 +652,7 @@ mod tests {
             props: vec![Prop {
                 epc: ElU8(0xD6),
                 pdc: ElU8(0x04),
-                edt: EDT(vec![
+                edt: EDT(smallvec![
                     ElU8(0x02),
                     ElU8(0x01),
                     ElU8(0x30),
@@ -345,4 +679,262 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_validate_get_response() {
+        let aircon = EOJ::new(0x0130, 1);
+        let request =
+            Packet::new_get_request(aircon, &[ElU8(0x80), ElU8(0xB0), ElU8(0xB3)]).unwrap();
+        let mut response = Packet {
+            tid: ElU16(1),
+            seoj: aircon,
+            deoj: request.seoj,
+            esv: ESV::GetSNA,
+            opc: ElU8(3),
+            props: vec![
+                Prop {
+                    epc: ElU8(0x80),
+                    pdc: ElU8(2),
+                    edt: EDT::from(vec![0x30, 0x30]),
+                },
+                Prop {
+                    epc: ElU8(0xB3),
+                    pdc: ElU8(0),
+                    edt: EDT::default(),
+                },
+                Prop {
+                    epc: ElU8(0xBB),
+                    pdc: ElU8(1),
+                    edt: EDT::from(vec![0x1A]),
+                },
+            ],
+        };
+        assert_eq!(
+            validate_get_response(&request, &mut response),
+            vec![
+                Anomaly::Unrequested(ElU8(0xBB)),
+                Anomaly::Missing(ElU8(0xB0)),
+                Anomaly::ImplausibleSize(ElU8(0x80), 2),
+            ]
+        );
+        assert_eq!(response.opc, ElU8(1));
+        assert!(response.get_prop(ElU8(0xBB)).is_none());
+        assert!(response.get_prop(ElU8(0x80)).is_none());
+
+        // 2 bytes of a 1-byte temperature
+        let request = Packet::new_get_request(aircon, &[ElU8(0xBB)]).unwrap();
+        let mut response = Packet::new_get_request(aircon, &[]).unwrap();
+        response.esv = ESV::GetRes;
+        response.seoj = aircon;
+        response.props = vec![Prop {
+            epc: ElU8(0xBB),
+            pdc: ElU8(2),
+            edt: EDT::from(vec![0x00, 0x1A]),
+        }];
+        assert_eq!(
+            validate_get_response(&request, &mut response),
+            vec![Anomaly::ImplausibleSize(ElU8(0xBB), 2)]
+        );
+        assert!(response.props.is_empty());
+
+        let mut response = Packet::new_get_request(aircon, &[]).unwrap();
+        response.esv = ESV::GetRes;
+        response.seoj = aircon;
+        response.props = vec![Prop {
+            epc: ElU8(0x80),
+            pdc: ElU8(1),
+            edt: EDT::from(vec![0x30]),
+        }];
+        let request = Packet::new_get_request(aircon, &[ElU8(0x80)]).unwrap();
+        assert!(validate_get_response(&request, &mut response).is_empty());
+    }
+
+    #[test]
+    fn test_write_errors() {
+        let aircon = EOJ::new(0x0130, 1);
+        let mut response = Packet {
+            tid: ElU16(1),
+            seoj: aircon,
+            deoj: eoj::CONTROLLER,
+            esv: ESV::SetCSNA,
+            opc: ElU8(2),
+            props: vec![
+                Prop {
+                    epc: ElU8(0x80),
+                    pdc: ElU8(0),
+                    edt: EDT::default(),
+                },
+                Prop {
+                    epc: ElU8(0xB3),
+                    pdc: ElU8(1),
+                    edt: EDT::from(vec![0x80]),
+                },
+            ],
+        };
+        let errors = write_errors(&response);
+        assert_eq!(
+            errors,
+            vec![WriteError::Rejected {
+                epc: ElU8(0xB3),
+                attempted: EDT::from(vec![0x80])
+            }]
+        );
+        assert_eq!(errors[0].to_string(), "rejected B3=0x80");
+
+        response.esv = ESV::SetRes;
+        response.props[1].pdc = ElU8(0);
+        response.props[1].edt = EDT::default();
+        assert!(write_errors(&response).is_empty());
+    }
+
+    #[test]
+    fn test_node_profile() {
+        let prop = |epc: u8, edt: Vec<u8>| Prop {
+            epc: ElU8(epc),
+            pdc: ElU8(edt.len() as u8),
+            edt: EDT::from(edt),
+        };
+        let mut packet = Packet {
+            tid: ElU16(1),
+            seoj: eoj::NODE_PROFILE,
+            deoj: eoj::CONTROLLER,
+            esv: ESV::GetRes,
+            opc: ElU8(4),
+            props: vec![
+                prop(0xD3, vec![0x00, 0x00, 0x02]),
+                prop(0xD4, vec![0x00, 0x03]),
+                prop(0xD6, vec![0x02, 0x01, 0x30, 0x01, 0x02, 0x88, 0x01]),
+                prop(0xD7, vec![0x02, 0x01, 0x30, 0x02, 0x88]),
+            ],
+        };
+        let profile = NodeProfile::try_from(&packet).unwrap();
+        assert_eq!(
+            profile.instances,
+            vec![EOJ::new(0x0130, 1), EOJ::new(0x0288, 1)]
+        );
+        assert_eq!(profile.classes, vec![ClassCode(0x0130), ClassCode(0x0288)]);
+        assert!(profile.inconsistencies.is_empty());
+
+        // a gateway advertising a class it doesn't list the instances of
+        packet.props = vec![
+            prop(0xD3, vec![0x00, 0x00, 0x02]),
+            prop(0xD4, vec![0x00, 0x04]),
+            prop(0xD6, vec![0x02, 0x01, 0x30, 0x01]),
+            prop(0xD7, vec![0x02, 0x01, 0x30, 0x02, 0x79]),
+        ];
+        assert_eq!(
+            NodeProfile::try_from(&packet).unwrap().inconsistencies,
+            vec![
+                Inconsistency::Count {
+                    epc: ElU8(0xD6),
+                    declared: 2,
+                    listed: 1
+                },
+                Inconsistency::Count {
+                    epc: ElU8(0xD3),
+                    declared: 2,
+                    listed: 1
+                },
+                Inconsistency::Count {
+                    epc: ElU8(0xD4),
+                    declared: 4,
+                    listed: 2
+                },
+                Inconsistency::Class {
+                    class: ClassCode(0x0279),
+                    only_in: ElU8(0xD7)
+                },
+            ]
+        );
+
+        // the optional properties being unavailable
+        packet.esv = ESV::GetSNA;
+        packet.props = vec![prop(0xD3, vec![]), prop(0xD6, vec![0x01, 0x01, 0x30, 0x01])];
+        let profile = NodeProfile::try_from(&packet).unwrap();
+        assert!(profile.classes.is_empty());
+        assert!(profile.inconsistencies.is_empty());
+    }
+
+    #[test]
+    fn test_boot_announcement() {
+        let prop = |epc: u8, edt: Vec<u8>| Prop {
+            epc: ElU8(epc),
+            pdc: ElU8(edt.len() as u8),
+            edt: EDT::from(edt),
+        };
+        let mut packet = Packet {
+            tid: ElU16(1),
+            seoj: eoj::NODE_PROFILE,
+            deoj: EOJ::new(0x0EF0, 1),
+            esv: ESV::Inf,
+            opc: ElU8(1),
+            props: vec![prop(0xD5, vec![0x02, 0x01, 0x30, 0x01, 0x02, 0x88, 0x01])],
+        };
+        assert_eq!(
+            BootAnnouncement::try_from(&packet).unwrap(),
+            BootAnnouncement {
+                eoj: eoj::NODE_PROFILE,
+                instances: vec![EOJ::new(0x0130, 1), EOJ::new(0x0288, 1)],
+            }
+        );
+
+        // a send-only node announcing the operation status alone
+        packet.seoj = EOJ::new(0x0EF0, 2);
+        packet.props = vec![prop(0x80, vec![0x30])];
+        assert!(BootAnnouncement::try_from(&packet)
+            .unwrap()
+            .instances
+            .is_empty());
+
+        packet.props = vec![prop(0x80, vec![0x31])];
+        assert!(BootAnnouncement::try_from(&packet).is_err());
+        // a response to a read of the operation status
+        packet.esv = ESV::GetRes;
+        packet.props = vec![prop(0x80, vec![0x30])];
+        assert!(BootAnnouncement::try_from(&packet).is_err());
+        packet.esv = ESV::Inf;
+        packet.seoj = EOJ::new(0x0130, 1);
+        assert!(BootAnnouncement::try_from(&packet).is_err());
+    }
+
+    #[test]
+    fn test_repeated_lists() {
+        let prop = |epc: u8, edt: Vec<u8>| Prop {
+            epc: ElU8(epc),
+            pdc: ElU8(edt.len() as u8),
+            edt: EDT::from(edt),
+        };
+        let packet = Packet {
+            tid: ElU16(1),
+            seoj: eoj::NODE_PROFILE,
+            deoj: eoj::CONTROLLER,
+            esv: ESV::GetRes,
+            opc: ElU8(2),
+            props: vec![
+                prop(0xD6, vec![0x01, 0x01, 0x30, 0x01]),
+                prop(0xD6, vec![0x01, 0x02, 0x88, 0x01]),
+            ],
+        };
+        assert_eq!(
+            DiscoveryResponse::try_from(&packet).unwrap().instances,
+            vec![EOJ::new(0x0130, 1), EOJ::new(0x0288, 1)]
+        );
+
+        let packet = Packet {
+            seoj: EOJ::new(0x0130, 1),
+            opc: ElU8(5),
+            props: vec![
+                prop(0x82, vec![0x00, 0x00, 0x52, 0x00]),
+                prop(0x9D, vec![0x01, 0x80]),
+                prop(0x9E, vec![0x01, 0x80]),
+                prop(0x9F, vec![0x02, 0x80, 0x9F]),
+                prop(0x9F, vec![0x02, 0x80, 0xBB]),
+            ],
+            ..packet
+        };
+        assert_eq!(
+            SyncResponse::try_from(&packet).unwrap().get_props,
+            vec![ElU8(0x80), ElU8(0x9F), ElU8(0xBB)]
+        );
+    }
 }
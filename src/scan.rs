@@ -0,0 +1,810 @@
+use crate::{
+    aggregate::Aggregator,
+    client::Client,
+    consts::{eoj, epc},
+    control,
+    decoder::{self, Props},
+    diagnosis::Timeout,
+    output::{Event, Output},
+    packet::{ElU8, Packet, EOJ, ESV},
+    response::{self, BootAnnouncement, DiscoveryResponse, NodeProfile, SyncResponse},
+    template::Template,
+};
+use log::{error, info, warn};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    future::Future,
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    sync::mpsc,
+    task::AbortHandle,
+    time::{self, Instant},
+};
+
+// the number of properties requested at once, small enough for the constrained stacks of appliances
+const WALK_CHUNK_SIZE: usize = 8;
+
+// a booting node announces both the instance list and the operation status, which start one rescan
+const REBOOT_HOLDOFF: Duration = Duration::from_secs(10);
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+// the answers with SNA in a row after which a property is taken as unsupported despite the map
+const SNA_LIMIT: u32 = 3;
+// how long the polls leave out an unsupported property before trying it again
+const SNA_REPROBE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// how far the walks have got, which tells the long ones from hung ones
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Progress {
+    pub nodes: usize,
+    // the objects being synchronized and walked
+    pub syncs_pending: usize,
+    pub syncs_completed: usize,
+    pub syncs_failed: usize,
+    // the properties the pending walks have yet to read
+    pub props_remaining: usize,
+    // seconds since the start of the scan
+    pub elapsed: u64,
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes, {} objects walked, {} pending, {} failed, {} properties to read, {}s elapsed",
+            self.nodes,
+            self.syncs_completed,
+            self.syncs_pending,
+            self.syncs_failed,
+            self.props_remaining,
+            self.elapsed
+        )
+    }
+}
+
+#[derive(Default)]
+struct Tracker {
+    nodes: HashSet<IpAddr>,
+    // the properties left to read by the walk of each pending object, none until it's synchronized
+    pending: HashMap<(IpAddr, EOJ), usize>,
+    completed: usize,
+    failed: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum Served {
+    // answered with SNA too many times in a row, so left out of the polls
+    Unsupported,
+    // answered again when tried after being left out
+    Again,
+}
+
+#[derive(Default)]
+struct Streak {
+    misses: u32,
+    skipped_until: Option<Instant>,
+}
+
+// the properties each object keeps answering with SNA, which some devices list in the get property
+// map without ever serving them
+#[derive(Default)]
+struct Unserved {
+    streaks: HashMap<(IpAddr, EOJ, ElU8), Streak>,
+}
+
+impl Unserved {
+    // the properties worth requesting now, the unsupported ones once their intervals pass
+    fn filter(&self, addr: IpAddr, eoj: EOJ, epcs: &[ElU8], now: Instant) -> Vec<ElU8> {
+        epcs.iter()
+            .filter(|&&epc| {
+                self.streaks
+                    .get(&(addr, eoj, epc))
+                    .and_then(|streak| streak.skipped_until)
+                    .is_none_or(|until| now >= until)
+            })
+            .copied()
+            .collect()
+    }
+
+    // counts the answer to a request of the property, telling whether it has just changed
+    fn record(
+        &mut self,
+        addr: IpAddr,
+        eoj: EOJ,
+        epc: ElU8,
+        served: bool,
+        now: Instant,
+    ) -> Option<Served> {
+        if served {
+            let streak = self.streaks.remove(&(addr, eoj, epc))?;
+            return streak.skipped_until.map(|_| Served::Again);
+        }
+        let streak = self.streaks.entry((addr, eoj, epc)).or_default();
+        streak.misses += 1;
+        let skipped = streak.skipped_until.is_some();
+        if skipped || streak.misses >= SNA_LIMIT {
+            streak.skipped_until = Some(now + SNA_REPROBE_INTERVAL);
+        }
+        (!skipped && streak.misses >= SNA_LIMIT).then_some(Served::Unsupported)
+    }
+}
+
+pub struct Scanner {
+    client: Arc<Client>,
+    output: Arc<Output>,
+    aggregator: Arc<Aggregator>,
+    // the latest known properties of every object, referred to when decoding notifications
+    objects: Mutex<HashMap<(IpAddr, EOJ), Props>>,
+    // the walk and the polling of every object, replaced when the object is walked again
+    tasks: Mutex<HashMap<(IpAddr, EOJ), AbortHandle>>,
+    rebooted: Mutex<HashMap<IpAddr, Instant>>,
+    poll_interval: Option<Duration>,
+    templates: Vec<Template>,
+    progress: Mutex<Tracker>,
+    unserved: Mutex<Unserved>,
+    started: Instant,
+    reports_progress: bool,
+}
+
+impl Scanner {
+    pub fn new(client: Arc<Client>, output: Arc<Output>, poll_interval: Option<Duration>) -> Self {
+        Self {
+            client,
+            output,
+            aggregator: Arc::default(),
+            objects: Mutex::new(HashMap::new()),
+            tasks: Mutex::new(HashMap::new()),
+            rebooted: Mutex::new(HashMap::new()),
+            poll_interval,
+            templates: vec![],
+            progress: Mutex::new(Tracker::default()),
+            unserved: Mutex::new(Unserved::default()),
+            started: Instant::now(),
+            reports_progress: false,
+        }
+    }
+
+    // totals the household power in the given aggregator, which the metrics are rendered from
+    pub fn with_aggregator(mut self, aggregator: Arc<Aggregator>) -> Self {
+        self.aggregator = aggregator;
+        self
+    }
+
+    // emits the progress of the walks every second while it changes
+    pub fn with_progress(mut self) -> Self {
+        self.reports_progress = true;
+        self
+    }
+
+    pub fn progress(&self) -> Progress {
+        let tracker = self.progress.lock().unwrap();
+        Progress {
+            nodes: tracker.nodes.len(),
+            syncs_pending: tracker.pending.len(),
+            syncs_completed: tracker.completed,
+            syncs_failed: tracker.failed,
+            props_remaining: tracker.pending.values().sum(),
+            elapsed: self.started.elapsed().as_secs(),
+        }
+    }
+
+    async fn report_progress(&self) {
+        let mut last = Progress::default();
+        let mut ticker = time::interval(PROGRESS_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let progress = self.progress();
+            // compared without the elapsed time, which only matters while something is pending
+            let changed = Progress {
+                elapsed: last.elapsed,
+                ..progress.clone()
+            } != last;
+            if changed || progress.syncs_pending > 0 {
+                self.output.emit(&Event::Progress(progress.clone()));
+            }
+            last = progress;
+        }
+    }
+
+    // polls the objects of the classes of the templates by their schedules instead
+    pub fn with_templates(mut self, templates: Vec<Template>) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    // discovers devices by multicast and scans every instance they answer with, until interrupted
+    pub async fn run(
+        self: Arc<Self>,
+        mut rx: mpsc::Receiver<(Vec<u8>, SocketAddr)>,
+    ) -> anyhow::Result<()> {
+        info!("Listening ECHONET Lite packets...");
+        if self.reports_progress {
+            let scanner = Arc::clone(&self);
+            tokio::spawn(async move { scanner.report_progress().await });
+        }
+        let client = Arc::clone(&self.client);
+        tokio::spawn(async move {
+            // send discovery packets to every available address family after 1 second sleep
+            time::sleep(time::Duration::from_secs(1)).await;
+            for addr in client.multicast_addrs() {
+                let packet = Packet::new_discovery_request();
+                if let Err(e) = client.send(addr, packet).await {
+                    error!("Failed to send a packet to {}: {:?}", addr, e);
+                }
+            }
+        });
+        loop {
+            tokio::select! {
+                res = rx.recv() => {
+                    let Some((msg, addr)) = res else {
+                        anyhow::bail!("all the sockets are closed");
+                    };
+                    let Some((ipv4, packet)) = self.client.receive(&msg, addr) else {
+                        continue;
+                    };
+                    if let Ok(r) = DiscoveryResponse::try_from(&packet) {
+                        self.scan_node(ipv4, &r.instances);
+                        self.output.emit(&Event::Discovery { addr: ipv4, response: r });
+                    } else if let Ok(r) = SyncResponse::try_from(&packet) {
+                        self.output.emit(&Event::Sync { addr: ipv4, response: r });
+                    } else if matches!(packet.esv, ESV::Inf | ESV::InfC) {
+                        if let Ok(announcement) = BootAnnouncement::try_from(&packet) {
+                            self.rescan(ipv4, announcement);
+                        }
+                        self.notify(ipv4, packet);
+                    } else if matches!(packet.esv, ESV::SetISNA | ESV::SetCSNA) {
+                        // refusals no request waits for, such as the ones of SetI
+                        for e in response::write_errors(&packet) {
+                            let e = control::describe(packet.seoj.class(), &e);
+                            warn!("[{}] {}: {}", ipv4, packet.seoj, e);
+                        }
+                    } else {
+                        warn!(
+                            "[{}] Received an unknown packet: {:?}",
+                            ipv4, packet
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // reads the node profile and walks every instance, replacing the polling of an earlier walk
+    fn scan_node(self: &Arc<Self>, addr: IpAddr, instances: &[EOJ]) {
+        let scanner = Arc::clone(self);
+        tokio::spawn(async move {
+            if let Err(e) = scanner.check_node_profile(addr).await {
+                error!("[{}] Failed to read the node profile: {:?}", addr, e);
+            }
+        });
+        let mut tasks = self.tasks.lock().unwrap();
+        let mut tracker = self.progress.lock().unwrap();
+        tracker.nodes.insert(addr);
+        for eoj in instances.iter().copied() {
+            tracker.pending.insert((addr, eoj), 0);
+            let scanner = Arc::clone(self);
+            let task = tokio::spawn(async move {
+                let result = scanner.sync_and_walk(addr, eoj).await;
+                {
+                    let mut tracker = scanner.progress.lock().unwrap();
+                    tracker.pending.remove(&(addr, eoj));
+                    match result {
+                        Ok(_) => tracker.completed += 1,
+                        Err(_) => tracker.failed += 1,
+                    }
+                }
+                match result {
+                    Ok(sync) => scanner.poll(addr, eoj, &sync).await,
+                    Err(e) => match e.downcast_ref::<Timeout>() {
+                        Some(_) => scanner.output.emit(&Event::Timeout {
+                            addr,
+                            eoj,
+                            diagnosis: scanner.client.diagnose(addr).await,
+                        }),
+                        None => error!("[{}] Failed to scan {:?}: {:?}", addr, eoj, e),
+                    },
+                }
+            });
+            if let Some(previous) = tasks.insert((addr, eoj), task.abort_handle()) {
+                previous.abort();
+            }
+        }
+    }
+
+    // forgets everything known about the rebooted node and scans it again, since a firmware update
+    // may have changed its instances and their property maps
+    fn rescan(self: &Arc<Self>, addr: IpAddr, announcement: BootAnnouncement) {
+        {
+            let now = Instant::now();
+            let mut rebooted = self.rebooted.lock().unwrap();
+            if rebooted
+                .get(&addr)
+                .is_some_and(|at| now.duration_since(*at) < REBOOT_HOLDOFF)
+            {
+                return;
+            }
+            rebooted.insert(addr, now);
+        }
+        self.objects.lock().unwrap().retain(|(a, _), _| *a != addr);
+        // the aborted walks
+        self.progress
+            .lock()
+            .unwrap()
+            .pending
+            .retain(|(a, _), _| *a != addr);
+        self.tasks.lock().unwrap().retain(|(a, _), task| {
+            if *a == addr {
+                task.abort();
+            }
+            *a != addr
+        });
+        let announced = announcement.instances.clone();
+        self.output.emit(&Event::Reboot { addr, announcement });
+        let scanner = Arc::clone(self);
+        tokio::spawn(async move {
+            // the operation status alone doesn't tell the instances
+            let instances = if announced.is_empty() {
+                match instances(&scanner.client, addr).await {
+                    Ok(instances) => instances,
+                    Err(e) => {
+                        error!("[{}] Failed to list the instances: {:?}", addr, e);
+                        return;
+                    }
+                }
+            } else {
+                announced
+            };
+            scanner.scan_node(addr, &instances);
+        });
+    }
+
+    // reads the self-node properties of the node profile to cross-check them
+    pub async fn check_node_profile(&self, addr: IpAddr) -> anyhow::Result<()> {
+        let packet = Packet::new_get_request(
+            eoj::NODE_PROFILE,
+            &[
+                epc::NUMBER_OF_INSTANCES,
+                epc::NUMBER_OF_CLASSES,
+                epc::INSTANCE_LIST_S,
+                epc::CLASS_LIST_S,
+            ],
+        )?;
+        let profile = NodeProfile::try_from(&self.client.request(addr, packet).await?)?;
+        self.output.emit(&Event::NodeProfile { addr, profile });
+        Ok(())
+    }
+
+    // synchronizes the property maps of the instance and reads all of its gettable properties
+    pub async fn sync_and_walk(&self, addr: IpAddr, eoj: EOJ) -> anyhow::Result<SyncResponse> {
+        let packet = Packet::new_sync_request(eoj);
+        let sync = SyncResponse::try_from(&self.client.request(addr, packet).await?)?;
+        let epcs = sync.get_props.clone();
+        self.output.emit(&Event::Sync {
+            addr,
+            response: sync.clone(),
+        });
+
+        if let Some(remaining) = self.progress.lock().unwrap().pending.get_mut(&(addr, eoj)) {
+            *remaining = epcs.len();
+        }
+        let mut props = Props::new();
+        for chunk in epcs.chunks(WALK_CHUNK_SIZE) {
+            let (read, unserved) = read_props(&self.client, addr, eoj, chunk).await;
+            self.count_served(addr, eoj, &read, &unserved);
+            props.extend(read);
+            let mut tracker = self.progress.lock().unwrap();
+            if let Some(remaining) = tracker.pending.get_mut(&(addr, eoj)) {
+                *remaining = remaining.saturating_sub(chunk.len());
+            }
+        }
+        // decoded after all the properties are collected since some of them refer to the others
+        for (&epc, edt) in &props {
+            self.output.emit(&Event::Property {
+                addr,
+                eoj,
+                epc,
+                value: decoder::decode(eoj.class(), epc, edt, &props),
+            });
+        }
+        if let Some(power) = self.aggregator.update(addr, eoj, &props) {
+            self.output.emit(&Event::Household(power));
+        }
+        self.objects.lock().unwrap().insert((addr, eoj), props);
+        Ok(sync)
+    }
+
+    // reads the properties which aren't announced periodically, until the scan is interrupted
+    async fn poll(&self, addr: IpAddr, eoj: EOJ, sync: &SyncResponse) {
+        let schedule = match self.templates.iter().find(|t| t.class() == eoj.class()) {
+            Some(template) => {
+                let schedule = templated_schedule(*template, &sync.get_props);
+                for (interval, epcs) in &schedule {
+                    info!(
+                        "[{}] Polling {:?} of {:?} every {:?} by {}",
+                        addr,
+                        epcs,
+                        eoj,
+                        interval,
+                        template.name()
+                    );
+                }
+                schedule
+            }
+            None => {
+                let Some(interval) = self.poll_interval else {
+                    return;
+                };
+                let epcs = polled_props(&sync.get_props, &sync.anno_props);
+                info!(
+                    "[{}] Polling {} of {} properties of {:?} every {:?}, the others being announced",
+                    addr,
+                    epcs.len(),
+                    sync.get_props.len(),
+                    eoj,
+                    interval
+                );
+                if epcs.is_empty() {
+                    return;
+                }
+                vec![(interval, epcs)]
+            }
+        };
+        let schedule = &schedule;
+        run_schedule(schedule, |i| async move {
+            let epcs =
+                self.unserved
+                    .lock()
+                    .unwrap()
+                    .filter(addr, eoj, &schedule[i].1, Instant::now());
+            if epcs.is_empty() {
+                return;
+            }
+            let (props, unserved) = read_props(&self.client, addr, eoj, &epcs).await;
+            self.count_served(addr, eoj, &props, &unserved);
+            self.update(addr, eoj, props);
+        })
+        .await;
+    }
+
+    // keeps track of the properties answered without data, warning of the ones which never are
+    fn count_served(&self, addr: IpAddr, eoj: EOJ, props: &Props, unserved: &[ElU8]) {
+        let answers = props
+            .keys()
+            .map(|&epc| (epc, true))
+            .chain(unserved.iter().map(|&epc| (epc, false)));
+        let mut tracker = self.unserved.lock().unwrap();
+        for (epc, served) in answers {
+            let name = || {
+                decoder::lookup(eoj.class(), epc).map_or("Unknown property", |d| d.localized_name())
+            };
+            match tracker.record(addr, eoj, epc, served, Instant::now()) {
+                Some(Served::Unsupported) => warn!(
+                    "[{}] {:?} {:?} ({}) is unsupported despite the property map, answered with SNA {} times in a row; polling it again in {:?}",
+                    addr,
+                    eoj,
+                    epc,
+                    name(),
+                    SNA_LIMIT,
+                    SNA_REPROBE_INTERVAL
+                ),
+                Some(Served::Again) => info!(
+                    "[{}] {:?} {:?} ({}) is answered again, polling it",
+                    addr,
+                    eoj,
+                    epc,
+                    name()
+                ),
+                None => {}
+            }
+        }
+    }
+
+    // handles properties announced by the device itself, such as periodic reports of sensors
+    fn notify(&self, addr: IpAddr, packet: Packet) {
+        self.update(addr, packet.seoj, packet.to_props());
+    }
+
+    // merges newly read properties into the known ones and emits them
+    fn update(&self, addr: IpAddr, eoj: EOJ, notified: Props) {
+        let mut objects = self.objects.lock().unwrap();
+        let props = objects.entry((addr, eoj)).or_default();
+        props.extend(notified.clone());
+        for (&epc, edt) in &notified {
+            self.output.emit(&Event::Property {
+                addr,
+                eoj,
+                epc,
+                value: decoder::decode(eoj.class(), epc, edt, props),
+            });
+        }
+        if let Some(power) = self.aggregator.update(addr, eoj, props) {
+            self.output.emit(&Event::Household(power));
+        }
+    }
+}
+
+// the properties describing the object itself, which are read by the walk once and for all
+const STATIC_PROPS: &[ElU8] = &[
+    epc::STANDARD_VERSION_INFORMATION,
+    epc::IDENTIFICATION_NUMBER,
+    epc::MANUFACTURER_CODE,
+    epc::PRODUCT_CODE,
+    epc::ANNO_PROPERTY_MAP,
+    epc::SET_PROPERTY_MAP,
+    epc::GET_PROPERTY_MAP,
+];
+
+// the gettable properties missing in the announcement property map, which have to be polled to follow
+fn polled_props(get_props: &[ElU8], anno_props: &[ElU8]) -> Vec<ElU8> {
+    get_props
+        .iter()
+        .filter(|epc| !anno_props.contains(epc) && !STATIC_PROPS.contains(epc))
+        .copied()
+        .collect()
+}
+
+// the properties of the template in the get property map, as the template covers the models of the
+// class lacking some of them
+fn templated_schedule(template: Template, get_props: &[ElU8]) -> Vec<(Duration, Vec<ElU8>)> {
+    template
+        .schedule()
+        .iter()
+        .map(|(interval, epcs)| {
+            let epcs: Vec<ElU8> = epcs
+                .iter()
+                .filter(|epc| get_props.contains(epc))
+                .copied()
+                .collect();
+            (*interval, epcs)
+        })
+        .filter(|(_, epcs)| !epcs.is_empty())
+        .collect()
+}
+
+// runs the reads of the schedule by their indices forever, on the clock of tokio, which the tests
+// pause. the first reads are due an interval after the walk, which has just read the properties
+async fn run_schedule<F, Fut>(schedule: &[(Duration, Vec<ElU8>)], mut read: F)
+where
+    F: FnMut(usize) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    if schedule.is_empty() {
+        return;
+    }
+    let now = Instant::now();
+    let mut due: Vec<Instant> = schedule
+        .iter()
+        .map(|(interval, _)| now + *interval)
+        .collect();
+    loop {
+        let (i, at) = due
+            .iter()
+            .copied()
+            .enumerate()
+            .min_by_key(|(_, at)| *at)
+            .unwrap();
+        time::sleep_until(at).await;
+        read(i).await;
+        // slow reads delay the next ones rather than bunching them up
+        due[i] = (at + schedule[i].0).max(Instant::now());
+    }
+}
+
+// reads the properties in chunks, skipping the ones which could not be read
+pub async fn get_props(client: &Client, addr: IpAddr, eoj: EOJ, epcs: &[ElU8]) -> Props {
+    read_props(client, addr, eoj, epcs).await.0
+}
+
+// the properties read, and the ones answered without data, the ones of failed requests being in
+// neither
+async fn read_props(client: &Client, addr: IpAddr, eoj: EOJ, epcs: &[ElU8]) -> (Props, Vec<ElU8>) {
+    let mut props = Props::new();
+    let mut unserved = vec![];
+    for chunk in epcs.chunks(WALK_CHUNK_SIZE) {
+        // the chunks are never too long for a request
+        let packet = Packet::new_get_request(eoj, chunk).unwrap();
+        let packet = match client.request(addr, packet).await {
+            Ok(packet) => packet,
+            Err(e) => {
+                warn!("[{}] Failed to get properties of {:?}: {:?}", addr, eoj, e);
+                continue;
+            }
+        };
+        // properties which could not be read are answered with empty EDTs in Get_SNA
+        props.extend(packet.to_props());
+        unserved.extend(
+            packet
+                .props
+                .iter()
+                .filter(|p| p.edt.0.is_empty())
+                .map(|p| p.epc),
+        );
+    }
+    (props, unserved)
+}
+
+// collects the instances listed by the nodes answering the multicast discovery within the wait,
+// the other packets being left to the client
+pub async fn discover(
+    client: Arc<Client>,
+    mut rx: mpsc::Receiver<(Vec<u8>, SocketAddr)>,
+    wait: Duration,
+) -> anyhow::Result<BTreeMap<IpAddr, Vec<EOJ>>> {
+    let discovered = Arc::new(Mutex::new(BTreeMap::<IpAddr, Vec<EOJ>>::new()));
+    let client_inner = Arc::clone(&client);
+    let discovered_inner = Arc::clone(&discovered);
+    tokio::spawn(async move {
+        while let Some((msg, addr)) = rx.recv().await {
+            let Some((ipv4, packet)) = client_inner.receive(&msg, addr) else {
+                continue;
+            };
+            if let Ok(r) = DiscoveryResponse::try_from(&packet) {
+                let mut discovered = discovered_inner.lock().unwrap();
+                let instances = discovered.entry(ipv4).or_default();
+                for eoj in r.instances {
+                    if !instances.contains(&eoj) {
+                        instances.push(eoj);
+                    }
+                }
+            }
+        }
+    });
+    for addr in client.multicast_addrs() {
+        client.send(addr, Packet::new_discovery_request()).await?;
+    }
+    time::sleep(wait).await;
+    let nodes = std::mem::take(&mut *discovered.lock().unwrap());
+    Ok(nodes)
+}
+
+// lists the instances of the node by unicast
+pub async fn instances(client: &Client, addr: IpAddr) -> anyhow::Result<Vec<EOJ>> {
+    let packet = Packet::new_get_request(eoj::NODE_PROFILE, &[epc::INSTANCE_LIST_S])?;
+    let res = client.request(addr, packet).await?;
+    Ok(DiscoveryResponse::try_from(&res)?.instances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_polled_props() {
+        let get_props = [
+            ElU8(0x80),
+            ElU8(0x81),
+            ElU8(0x83),
+            ElU8(0x9F),
+            ElU8(0xB0),
+            ElU8(0xBB),
+        ];
+        let anno_props = [ElU8(0x80), ElU8(0x81), ElU8(0x88)];
+        assert_eq!(
+            polled_props(&get_props, &anno_props),
+            vec![ElU8(0xB0), ElU8(0xBB)]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_schedule() {
+        let started = Instant::now();
+        let reads = Mutex::new(vec![]);
+        let reads = &reads;
+        let schedule = [
+            (Duration::from_secs(10), vec![ElU8(0xE7)]),
+            (Duration::from_secs(30), vec![ElU8(0xE0)]),
+        ];
+        let running = run_schedule(&schedule, |i| async move {
+            reads.lock().unwrap().push((started.elapsed().as_secs(), i));
+        });
+        assert!(time::timeout(Duration::from_secs(61), running)
+            .await
+            .is_err());
+        assert_eq!(
+            *reads.lock().unwrap(),
+            vec![
+                (10, 0),
+                (20, 0),
+                (30, 0),
+                (30, 1),
+                (40, 0),
+                (50, 0),
+                (60, 0),
+                (60, 1)
+            ]
+        );
+
+        // reads taking longer than the interval
+        let started = Instant::now();
+        reads.lock().unwrap().clear();
+        let schedule = [(Duration::from_secs(10), vec![ElU8(0xE7)])];
+        let running = run_schedule(&schedule, |i| async move {
+            reads.lock().unwrap().push((started.elapsed().as_secs(), i));
+            time::sleep(Duration::from_secs(15)).await;
+        });
+        assert!(time::timeout(Duration::from_secs(50), running)
+            .await
+            .is_err());
+        assert_eq!(*reads.lock().unwrap(), vec![(10, 0), (25, 0), (40, 0)]);
+    }
+
+    #[test]
+    fn test_progress() {
+        let progress = Progress {
+            nodes: 2,
+            syncs_pending: 1,
+            syncs_completed: 3,
+            syncs_failed: 0,
+            props_remaining: 12,
+            elapsed: 7,
+        };
+        assert_eq!(
+            progress.to_string(),
+            "2 nodes, 3 objects walked, 1 pending, 0 failed, 12 properties to read, 7s elapsed"
+        );
+        assert_eq!(
+            serde_json::to_value(&progress).unwrap()["props_remaining"],
+            12
+        );
+    }
+
+    #[test]
+    fn test_unserved() {
+        let now = Instant::now();
+        let addr: IpAddr = "192.168.1.10".parse().unwrap();
+        let eoj = EOJ::new(0x0130, 1);
+        let epcs = [ElU8(0x80), ElU8(0xBB)];
+        let mut unserved = Unserved::default();
+        for _ in 1..SNA_LIMIT {
+            assert_eq!(unserved.record(addr, eoj, ElU8(0xBB), false, now), None);
+        }
+        // an answer breaks the streak
+        assert_eq!(unserved.record(addr, eoj, ElU8(0xBB), true, now), None);
+        for _ in 1..SNA_LIMIT {
+            unserved.record(addr, eoj, ElU8(0xBB), false, now);
+        }
+        assert_eq!(
+            unserved.record(addr, eoj, ElU8(0xBB), false, now),
+            Some(Served::Unsupported)
+        );
+        assert_eq!(unserved.filter(addr, eoj, &epcs, now), [ElU8(0x80)]);
+        // the other objects are unaffected
+        assert_eq!(unserved.filter(addr, EOJ::new(0x0130, 2), &epcs, now), epcs);
+
+        // tried again after the interval, and left out for another one while it still fails
+        let later = now + SNA_REPROBE_INTERVAL;
+        assert_eq!(unserved.filter(addr, eoj, &epcs, later), epcs);
+        assert_eq!(unserved.record(addr, eoj, ElU8(0xBB), false, later), None);
+        assert_eq!(unserved.filter(addr, eoj, &epcs, later), [ElU8(0x80)]);
+        let later = later + SNA_REPROBE_INTERVAL;
+        assert_eq!(
+            unserved.record(addr, eoj, ElU8(0xBB), true, later),
+            Some(Served::Again)
+        );
+        assert_eq!(unserved.filter(addr, eoj, &epcs, later), epcs);
+    }
+
+    #[test]
+    fn test_templated_schedule() {
+        let get_props = [ElU8(0x80), ElU8(0xB3), ElU8(0xE0), ElU8(0xE7)];
+        assert_eq!(
+            templated_schedule(Template::SmartMeterDefault, &get_props),
+            vec![
+                (Duration::from_secs(10), vec![ElU8(0xE7)]),
+                (Duration::from_secs(1800), vec![ElU8(0xE0)]),
+            ]
+        );
+        // lacking the room temperature
+        assert_eq!(
+            templated_schedule(Template::AirconDefault, &get_props),
+            vec![(Duration::from_secs(60), vec![ElU8(0x80), ElU8(0xB3)])]
+        );
+        assert!(templated_schedule(Template::AirconDefault, &[ElU8(0x81)]).is_empty());
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{
+    client::Client,
+    control,
+    output::Output,
+    packet::{ElU8, EDT, EOJ},
+};
+use jiff::SignedDuration;
+use log::{error, info};
+use std::{net::IpAddr, time::Duration};
+
+// a batch file has one operation per line, blank lines and lines starting with '#' being ignored:
+//   get <addr> <eoj> <epc>...
+//   set <addr> <eoj> <epc>=<edt>...
+//   wait <duration>            (e.g. 500ms, 2s)
+//   on-error stop|continue     (applies to the following lines)
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    Get {
+        addr: IpAddr,
+        eoj: EOJ,
+        epcs: Vec<ElU8>,
+    },
+    Set {
+        addr: IpAddr,
+        eoj: EOJ,
+        props: Vec<(ElU8, EDT)>,
+    },
+    Wait(Duration),
+    OnError {
+        continue_on_error: bool,
+    },
+}
+
+// parses the whole file up front so that a typo never leaves the devices half configured
+pub fn parse(text: &str) -> anyhow::Result<Vec<(usize, Step)>> {
+    let mut steps = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let step = parse_step(line).map_err(|e| anyhow::anyhow!("line {}: {}", i + 1, e))?;
+        steps.push((i + 1, step));
+    }
+    Ok(steps)
+}
+
+fn parse_step(line: &str) -> anyhow::Result<Step> {
+    let mut words = line.split_whitespace();
+    let op = words.next().unwrap_or_default();
+    let args: Vec<&str> = words.collect();
+    match (op, &args[..]) {
+        ("get", [addr, eoj, epcs @ ..]) if !epcs.is_empty() => Ok(Step::Get {
+            addr: addr.parse()?,
+            eoj: eoj.parse()?,
+            epcs: epcs.iter().map(|s| s.parse()).collect::<Result<_, _>>()?,
+        }),
+        ("set", [addr, eoj, props @ ..]) if !props.is_empty() => Ok(Step::Set {
+            addr: addr.parse()?,
+            eoj: eoj.parse()?,
+            props: props
+                .iter()
+                .map(|s| control::parse_assignment(s))
+                .collect::<Result<_, _>>()?,
+        }),
+        ("wait", [duration]) => Ok(Step::Wait(duration.parse::<SignedDuration>()?.try_into()?)),
+        ("on-error", ["stop"]) => Ok(Step::OnError {
+            continue_on_error: false,
+        }),
+        ("on-error", ["continue"]) => Ok(Step::OnError {
+            continue_on_error: true,
+        }),
+        ("get" | "set" | "wait" | "on-error", _) => anyhow::bail!("invalid arguments to {}", op),
+        _ => anyhow::bail!("unknown operation {:?}", op),
+    }
+}
+
+pub async fn run(
+    client: &Client,
+    output: &Output,
+    steps: Vec<(usize, Step)>,
+    mut continue_on_error: bool,
+) -> anyhow::Result<()> {
+    let total = steps.len();
+    let mut failed = 0;
+    for (line, step) in steps {
+        let result = match step {
+            Step::Get { addr, eoj, epcs } => control::get(client, output, addr, eoj, &epcs).await,
+            Step::Set { addr, eoj, props } => control::set(client, addr, eoj, props).await,
+            Step::Wait(duration) => {
+                tokio::time::sleep(duration).await;
+                Ok(())
+            }
+            Step::OnError {
+                continue_on_error: c,
+            } => {
+                continue_on_error = c;
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            if !continue_on_error {
+                return Err(e.context(format!("line {}", line)));
+            }
+            error!("Failed at line {}: {:?}", line, e);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} steps failed", failed, total);
+    }
+    info!("Completed {} steps", total);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = "# commissioning\n\
+                    get 192.168.1.10 013001 80 B0\n\
+                    \n\
+                    on-error continue\n\
+                    set 192.168.1.10 013001 80=30 B3=1A\n\
+                    wait 500ms\n";
+        let addr: IpAddr = "192.168.1.10".parse().unwrap();
+        let eoj = EOJ::new(0x0130, 1);
+        assert_eq!(
+            parse(text).unwrap(),
+            vec![
+                (
+                    2,
+                    Step::Get {
+                        addr,
+                        eoj,
+                        epcs: vec![ElU8(0x80), ElU8(0xB0)]
+                    }
+                ),
+                (
+                    4,
+                    Step::OnError {
+                        continue_on_error: true
+                    }
+                ),
+                (
+                    5,
+                    Step::Set {
+                        addr,
+                        eoj,
+                        props: vec![
+                            (ElU8(0x80), EDT::from(vec![0x30])),
+                            (ElU8(0xB3), EDT::from(vec![0x1A]))
+                        ]
+                    }
+                ),
+                (6, Step::Wait(Duration::from_millis(500))),
+            ]
+        );
+
+        let err = parse("get 192.168.1.10 013001 80\nset 192.168.1.10 013001\n").unwrap_err();
+        assert_eq!(err.to_string(), "line 2: invalid arguments to set");
+        assert!(parse("reboot 192.168.1.10").is_err());
+        assert!(parse("wait -1s").is_err());
+        assert!(parse("on-error retry").is_err());
+    }
+}
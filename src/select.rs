@@ -0,0 +1,387 @@
+use crate::{
+    decoder::{self, PropertyValue, Props, Value},
+    output::Event,
+    packet::{ElU8, EOJ},
+};
+use std::{cmp::Ordering, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Event,
+    Addr,
+    Eoj,
+    Class,
+    Instance,
+    Epc,
+    Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Field(Field),
+    // the latest value of the property of the object the event is about
+    Prop(ElU8),
+    Literal(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, Op, Operand),
+    // whether the operand has a value at all, such as `epc(0xE0)`
+    Present(Operand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Scalar {
+    Number(f64),
+    Text(String),
+}
+
+impl Scalar {
+    fn literal(s: &str) -> Self {
+        let n = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16).ok().map(|n| n as f64),
+            None => s.parse().ok(),
+        };
+        n.map_or_else(|| Self::Text(s.to_string()), Self::Number)
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Self::Number(n) => n.to_string(),
+            Self::Text(s) => s.to_lowercase(),
+        }
+    }
+
+    // numbers compare as numbers and anything else as case-insensitive text
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => a.partial_cmp(b),
+            _ => Some(self.text().cmp(&other.text())),
+        }
+    }
+}
+
+fn property_scalar(value: &PropertyValue) -> Scalar {
+    match value.value {
+        Some(Value::Number(n)) => Scalar::Number(n),
+        Some(Value::Text(s)) => Scalar::Text(s.to_string()),
+        // undecoded values are compared by their raw data
+        None => Scalar::literal(&format!("0x{}", value.raw.to_hex())),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    Op(Op),
+    Word(String),
+}
+
+fn tokenize(s: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+            '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+            '=' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Eq),
+            '!' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Ne),
+            '!' => Token::Not,
+            '<' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Le),
+            '<' => Token::Op(Op::Lt),
+            '>' if chars.next_if_eq(&'=').is_some() => Token::Op(Op::Ge),
+            '>' => Token::Op(Op::Gt),
+            '"' | '\'' => {
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(ch) => word.push(ch),
+                        None => anyhow::bail!("unterminated string"),
+                    }
+                }
+                Token::Word(word)
+            }
+            c if c.is_alphanumeric() || "_.:-+".contains(c) => {
+                let mut word = c.to_string();
+                while let Some(ch) =
+                    chars.next_if(|&ch| ch.is_alphanumeric() || "_.:-+".contains(ch))
+                {
+                    word.push(ch);
+                }
+                Token::Word(word)
+            }
+            c => anyhow::bail!("unexpected character '{}'", c),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, token: Token) -> anyhow::Result<()> {
+        match self.next() {
+            Some(t) if t == token => Ok(()),
+            t => anyhow::bail!("expected {:?}, found {:?}", token, t),
+        }
+    }
+
+    // `||` binds looser than `&&`, which binds looser than `!`
+    fn or(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> anyhow::Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> anyhow::Result<Expr> {
+        match self.peek() {
+            Some(Token::Not) => {
+                self.next();
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some(Token::LParen) => {
+                self.next();
+                let expr = self.or()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            _ => {
+                let lhs = self.operand()?;
+                let Some(&Token::Op(op)) = self.peek() else {
+                    return Ok(Expr::Present(lhs));
+                };
+                self.next();
+                Ok(Expr::Compare(lhs, op, self.operand()?))
+            }
+        }
+    }
+
+    fn operand(&mut self) -> anyhow::Result<Operand> {
+        let word = match self.next() {
+            Some(Token::Word(word)) => word,
+            t => anyhow::bail!("expected a field or a value, found {:?}", t),
+        };
+        let field = match word.as_str() {
+            "epc" if self.peek() == Some(&Token::LParen) => {
+                self.next();
+                let epc = match self.next() {
+                    Some(Token::Word(epc)) => epc,
+                    t => anyhow::bail!("expected an EPC, found {:?}", t),
+                };
+                self.expect(Token::RParen)?;
+                return Ok(Operand::Prop(epc.parse()?));
+            }
+            "event" => Field::Event,
+            "addr" => Field::Addr,
+            "eoj" => Field::Eoj,
+            "class" => Field::Class,
+            "instance" => Field::Instance,
+            "epc" => Field::Epc,
+            "value" => Field::Value,
+            _ => return Ok(Operand::Literal(word)),
+        };
+        Ok(Operand::Field(field))
+    }
+}
+
+// an expression selecting the events to output, such as `class == 0x0130 && epc(0x80) == on`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selector(Expr);
+
+impl FromStr for Selector {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let expr = parser.or()?;
+        if let Some(t) = parser.peek() {
+            anyhow::bail!("unexpected {:?}", t);
+        }
+        Ok(Self(expr))
+    }
+}
+
+impl Selector {
+    // props are the latest known properties of the object the event is about
+    pub fn matches(&self, event: &Event, props: &Props) -> bool {
+        eval(&self.0, event, props)
+    }
+}
+
+fn eval(expr: &Expr, event: &Event, props: &Props) -> bool {
+    match expr {
+        Expr::Or(a, b) => eval(a, event, props) || eval(b, event, props),
+        Expr::And(a, b) => eval(a, event, props) && eval(b, event, props),
+        Expr::Not(e) => !eval(e, event, props),
+        Expr::Present(operand) => resolve(operand, event, props).is_some(),
+        // comparisons with the fields the event doesn't have are false
+        Expr::Compare(a, op, b) => {
+            let (Some(a), Some(b)) = (resolve(a, event, props), resolve(b, event, props)) else {
+                return false;
+            };
+            let Some(ordering) = a.compare(&b) else {
+                return false;
+            };
+            match op {
+                Op::Eq => ordering.is_eq(),
+                Op::Ne => ordering.is_ne(),
+                Op::Lt => ordering.is_lt(),
+                Op::Le => ordering.is_le(),
+                Op::Gt => ordering.is_gt(),
+                Op::Ge => ordering.is_ge(),
+            }
+        }
+    }
+}
+
+fn resolve(operand: &Operand, event: &Event, props: &Props) -> Option<Scalar> {
+    let eoj = event.eoj();
+    match operand {
+        Operand::Literal(s) => Some(Scalar::literal(s)),
+        Operand::Field(Field::Event) => Some(Scalar::Text(event.name().to_string())),
+        Operand::Field(Field::Addr) => event.addr().map(|a| Scalar::Text(a.to_string())),
+        Operand::Field(Field::Eoj) => eoj.map(|e| Scalar::Text(e.to_string())),
+        Operand::Field(Field::Class) => eoj.map(|e| Scalar::Number(e.class().into())),
+        Operand::Field(Field::Instance) => eoj.map(|e| Scalar::Number(e.instance().into())),
+        Operand::Field(Field::Epc) => match event {
+            Event::Property { epc, .. } | Event::Restore { epc, .. } => {
+                Some(Scalar::Number(epc.0.into()))
+            }
+            _ => None,
+        },
+        Operand::Field(Field::Value) => match event {
+            Event::Property { value, .. } => Some(property_scalar(value)),
+            _ => None,
+        },
+        Operand::Prop(epc) => {
+            let eoj: EOJ = eoj?;
+            let edt = props.get(epc)?;
+            Some(property_scalar(&decoder::decode(
+                eoj.class(),
+                *epc,
+                edt,
+                props,
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{aggregate::HouseholdPower, output::Event, packet::EDT};
+
+    fn property(class: u16, epc: u8, edt: &[u8]) -> (Event, Props) {
+        let eoj = EOJ::new(class, 1);
+        let props = Props::from([(ElU8(epc), EDT::from(edt))]);
+        let event = Event::Property {
+            addr: "192.168.1.10".parse().unwrap(),
+            eoj,
+            epc: ElU8(epc),
+            value: decoder::decode(class, ElU8(epc), &EDT::from(edt), &props),
+        };
+        (event, props)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!("class == 0x0130 && epc(0x80) == on"
+            .parse::<Selector>()
+            .is_ok());
+        assert!("!(event == property) || addr == '192.168.1.10'"
+            .parse::<Selector>()
+            .is_ok());
+        assert!("class ==".parse::<Selector>().is_err());
+        assert!("(class == 0x0130".parse::<Selector>().is_err());
+        assert!("class = 0x0130".parse::<Selector>().is_err());
+        assert!("epc(0x80".parse::<Selector>().is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let matches = |s: &str, (event, props): &(Event, Props)| {
+            s.parse::<Selector>().unwrap().matches(event, props)
+        };
+        let status = property(0x0130, 0x80, &[0x30]);
+        assert!(matches("class == 0x0130 && epc(0x80) == on", &status));
+        assert!(matches("class == 304 && value == ON", &status));
+        assert!(!matches("class == 0x0130 && epc(0x80) == off", &status));
+        assert!(matches("eoj == 0130:01 && addr == 192.168.1.10", &status));
+        assert!(matches("event == property && epc == 0x80", &status));
+        // the other properties of the object are looked up
+        let (event, mut props) = property(0x0130, 0xBB, &[0x1A]);
+        props.insert(ElU8(0x80), EDT::from(vec![0x30]));
+        let temperature = (event, props);
+        assert!(matches("epc(0x80) == on && value >= 25.5", &temperature));
+        assert!(matches("value < 30 || class == 0x0288", &temperature));
+        assert!(!matches("epc(0xB0)", &temperature));
+        assert!(matches("!epc(0xB0) && epc(0x80)", &temperature));
+        // undecoded values are compared by their raw data
+        let unknown = property(0x0130, 0xF0, &[0x01, 0x02]);
+        assert!(matches("value == 0x0102", &unknown));
+        // missing fields compare false either way
+        let household = (
+            Event::Household(HouseholdPower {
+                grid: None,
+                generation: 0.0,
+                storage: 0.0,
+                consumption: None,
+            }),
+            Props::new(),
+        );
+        assert!(!matches("class == 0x0130", &household));
+        assert!(!matches("class != 0x0130", &household));
+        assert!(matches("event == household", &household));
+    }
+}
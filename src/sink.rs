@@ -0,0 +1,386 @@
+use crate::{output::Event, spool::Spool};
+use log::{error, info, warn};
+use serde::Serialize;
+use std::{
+    future::Future,
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+// how often the spooled records are retried while no new record comes
+const SPOOL_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SinkStats {
+    pub name: &'static str,
+    // records waiting to be written
+    pub queued: u64,
+    // records the destination took, including the ones replayed from the spool
+    pub written: u64,
+    // records dropped as the queue was full, or the spool
+    pub dropped: u64,
+    // records waiting in the spool for the destination to be back
+    pub spooled: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    accepted: AtomicU64,
+    // records done with, whether they were written, spooled or failed
+    taken: AtomicU64,
+    written: AtomicU64,
+    dropped: AtomicU64,
+    dropping: AtomicBool,
+    spooled: AtomicU64,
+}
+
+// a destination of the events besides the built-in ones, such as the API of a cloud service, which
+// is handed the events selected for the output one at a time
+pub trait Sink: Send + 'static {
+    fn handle(&mut self, event: Event) -> impl Future<Output = anyhow::Result<()>>;
+}
+
+// writes the records on a thread of its own through a bounded queue, so that a slow consumer
+// neither stalls the reception of the packets nor makes the records pile up in memory
+pub struct Writer<T = Vec<u8>> {
+    name: &'static str,
+    tx: Mutex<Option<SyncSender<T>>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+    counters: Arc<Counters>,
+}
+
+impl Writer {
+    pub fn spawn(name: &'static str, capacity: usize, mut w: impl Write + Send + 'static) -> Self {
+        Self::start(name, capacity, move |rx, counters| {
+            for record in rx {
+                match w.write_all(&record).and_then(|_| w.flush()) {
+                    Ok(()) => {
+                        counters.written.fetch_add(1, Ordering::Relaxed);
+                    }
+                    Err(e) => error!("Failed to write to {}: {:?}", name, e),
+                }
+                counters.taken.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+    }
+
+    // keeps the records failing to be written in the spool, retrying them before the newer ones
+    // so that the destination receives them in order
+    pub fn spawn_spooled(
+        name: &'static str,
+        capacity: usize,
+        mut w: impl Write + Send + 'static,
+        mut spool: Spool,
+    ) -> Self {
+        Self::start(name, capacity, move |rx, counters| {
+            if !spool.is_empty() {
+                info!("{} records of {} are spooled", spool.records(), name);
+            }
+            counters.spooled.store(spool.records(), Ordering::Relaxed);
+            let mut full = false;
+            loop {
+                let record = match rx.recv_timeout(SPOOL_RETRY_INTERVAL) {
+                    Ok(record) => Some(record),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                if !spool.is_empty() {
+                    match spool.replay(&mut w) {
+                        Ok(n) => {
+                            counters.written.fetch_add(n, Ordering::Relaxed);
+                            if n > 0 && spool.is_empty() {
+                                info!("Wrote the {} records spooled for {}", n, name);
+                            }
+                        }
+                        Err(e) => error!("Failed to read the spool of {}: {:?}", name, e),
+                    }
+                }
+                if let Some(record) = record {
+                    if spool.is_empty() && w.write_all(&record).and_then(|_| w.flush()).is_ok() {
+                        counters.written.fetch_add(1, Ordering::Relaxed);
+                        full = false;
+                    } else {
+                        match spool.push(&record) {
+                            Ok(true) => {
+                                full = false;
+                                if spool.records() == 1 {
+                                    warn!("Failed to write to {}, spooling the records", name);
+                                }
+                            }
+                            Ok(false) => {
+                                counters.dropped.fetch_add(1, Ordering::Relaxed);
+                                if !full {
+                                    warn!("The spool of {} is full, dropping records", name);
+                                }
+                                full = true;
+                            }
+                            Err(e) => error!("Failed to spool a record of {}: {:?}", name, e),
+                        }
+                    }
+                    counters.taken.fetch_add(1, Ordering::Relaxed);
+                }
+                counters.spooled.store(spool.records(), Ordering::Relaxed);
+            }
+        })
+    }
+}
+
+impl Writer<Event> {
+    // runs the sink on a runtime of its own, whose thread nothing else shares
+    pub fn spawn_sink(name: &'static str, capacity: usize, mut sink: impl Sink) -> Self {
+        Self::start(name, capacity, move |rx, counters| {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    error!("Failed to start {}: {:?}", name, e);
+                    return;
+                }
+            };
+            runtime.block_on(async {
+                for event in rx {
+                    match sink.handle(event).await {
+                        Ok(()) => {
+                            counters.written.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(e) => error!("Failed to write to {}: {:?}", name, e),
+                    }
+                    counters.taken.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        })
+    }
+}
+
+impl<T: Send + 'static> Writer<T> {
+    fn start(
+        name: &'static str,
+        capacity: usize,
+        write: impl FnOnce(Receiver<T>, &Counters) + Send + 'static,
+    ) -> Self {
+        let (tx, rx) = mpsc::sync_channel::<T>(capacity);
+        let counters = Arc::new(Counters::default());
+        let writer = {
+            let counters = Arc::clone(&counters);
+            thread::spawn(move || write(rx, &counters))
+        };
+        Self {
+            name,
+            tx: Mutex::new(Some(tx)),
+            writer: Mutex::new(Some(writer)),
+            counters,
+        }
+    }
+}
+
+impl<T> Writer<T> {
+    // queues a record, dropping it if the queue is full
+    pub fn send(&self, record: T) {
+        let tx = self.tx.lock().unwrap();
+        let Some(tx) = tx.as_ref() else {
+            return;
+        };
+        match tx.try_send(record) {
+            Ok(()) => {
+                self.counters.accepted.fetch_add(1, Ordering::Relaxed);
+                self.counters.dropping.store(false, Ordering::Relaxed);
+            }
+            Err(TrySendError::Full(_)) => {
+                self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                // warns once per run of drops rather than for every record
+                if !self.counters.dropping.swap(true, Ordering::Relaxed) {
+                    warn!("{} is falling behind, dropping records", self.name);
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+
+    // waits for the queued records to be written, after which the records sent are discarded
+    pub fn close(&self) {
+        self.tx.lock().unwrap().take();
+        if let Some(writer) = self.writer.lock().unwrap().take() {
+            let _ = writer.join();
+        }
+    }
+
+    pub fn stats(&self) -> SinkStats {
+        SinkStats {
+            name: self.name,
+            queued: self
+                .counters
+                .accepted
+                .load(Ordering::Relaxed)
+                .saturating_sub(self.counters.taken.load(Ordering::Relaxed)),
+            written: self.counters.written.load(Ordering::Relaxed),
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            spooled: self.counters.spooled.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// the records are not lost on the early returns either
+impl<T> Drop for Writer<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{diagnosis::Diagnosis, packet::EOJ, scan::Progress};
+    use std::io;
+
+    // blocks in the first write until the gate opens
+    struct Gated {
+        started: Option<SyncSender<()>>,
+        gate: Receiver<()>,
+        written: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for Gated {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if let Some(started) = self.started.take() {
+                started.send(()).unwrap();
+                let _ = self.gate.recv();
+            }
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sink_drops_when_full() {
+        let (started_tx, started) = mpsc::sync_channel(1);
+        let (open, gate) = mpsc::channel();
+        let written = Arc::new(Mutex::new(vec![]));
+        let sink = Writer::spawn(
+            "test",
+            2,
+            Gated {
+                started: Some(started_tx),
+                gate,
+                written: Arc::clone(&written),
+            },
+        );
+        sink.send(b"a".to_vec());
+        started.recv().unwrap();
+        // the writer is stuck on the first record, so only two more fit
+        sink.send(b"b".to_vec());
+        sink.send(b"c".to_vec());
+        sink.send(b"d".to_vec());
+        assert_eq!(
+            sink.stats(),
+            SinkStats {
+                name: "test",
+                queued: 3,
+                written: 0,
+                dropped: 1,
+                spooled: 0,
+            }
+        );
+        open.send(()).unwrap();
+        sink.close();
+        assert_eq!(*written.lock().unwrap(), b"abc");
+        assert_eq!(sink.stats().queued, 0);
+        assert_eq!(sink.stats().written, 3);
+        // nothing is written once closed
+        sink.send(b"e".to_vec());
+        assert_eq!(sink.stats().written, 3);
+    }
+
+    // fails the writes until it's up
+    struct Uplink {
+        up: Arc<AtomicBool>,
+        written: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for Uplink {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if !self.up.load(Ordering::Relaxed) {
+                return Err(io::Error::new(io::ErrorKind::NotConnected, "down"));
+            }
+            self.written.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // collects the names of the events, failing the progress ones
+    struct Collect(Arc<Mutex<Vec<&'static str>>>);
+
+    impl Sink for Collect {
+        async fn handle(&mut self, event: Event) -> anyhow::Result<()> {
+            // on the timers of the runtime of the sink
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            if let Event::Progress(_) = event {
+                anyhow::bail!("rejected");
+            }
+            self.0.lock().unwrap().push(event.name());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_sink_handles_events() {
+        let names = Arc::new(Mutex::new(vec![]));
+        let sink = Writer::spawn_sink("test", 8, Collect(Arc::clone(&names)));
+        let timeout = Event::Timeout {
+            addr: "192.168.1.10".parse().unwrap(),
+            eoj: EOJ::new(0x0130, 1),
+            diagnosis: Diagnosis::new(false, false, None, None),
+        };
+        sink.send(timeout.clone());
+        sink.send(Event::Progress(Progress::default()));
+        sink.send(timeout);
+        sink.close();
+        // a failing event doesn't stop the others
+        assert_eq!(*names.lock().unwrap(), ["timeout", "timeout"]);
+        assert_eq!(sink.stats().written, 2);
+    }
+
+    #[test]
+    fn test_sink_spools_while_down() {
+        let path = std::env::temp_dir().join(format!("elscan-sink-{}.spool", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let up = Arc::new(AtomicBool::new(false));
+        let written = Arc::new(Mutex::new(vec![]));
+        let uplink = Uplink {
+            up: Arc::clone(&up),
+            written: Arc::clone(&written),
+        };
+        let sink = Writer::spawn_spooled("test", 8, uplink, Spool::open(&path, 1024).unwrap());
+        sink.send(b"a".to_vec());
+        sink.send(b"b".to_vec());
+        while sink.stats().spooled < 2 {
+            thread::yield_now();
+        }
+        // spooled rather than written
+        assert_eq!(sink.stats().written, 0);
+        assert_eq!(sink.stats().queued, 0);
+        up.store(true, Ordering::Relaxed);
+        // the spooled records go first
+        sink.send(b"c".to_vec());
+        sink.close();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(*written.lock().unwrap(), b"abc");
+        assert_eq!(sink.stats().spooled, 0);
+        assert_eq!(sink.stats().written, 3);
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{
+    client::Client,
+    consts::epc,
+    decoder::Props,
+    output::{Event, Output},
+    packet::{ElU8, Packet, EOJ},
+    response::parse_property_map,
+    scan,
+};
+use jiff::Timestamp;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+
+// the current time and date are settable but obsolete once restored
+const EXCLUDED_EPCS: &[ElU8] = &[epc::CURRENT_TIME, epc::CURRENT_DATE];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub addr: IpAddr,
+    pub eoj: EOJ,
+    pub taken_at: Timestamp,
+    pub props: Props,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreResult {
+    Restored,
+    // the device answered SetC_SNA
+    Refused,
+    // the device accepted the value but reads back another one
+    Mismatch,
+    // the request couldn't be made or wasn't answered
+    Failed,
+}
+
+// reads all the properties which are both settable and gettable
+pub async fn take(client: &Client, addr: IpAddr, eoj: EOJ) -> anyhow::Result<Snapshot> {
+    let packet = Packet::new_get_request(eoj, &[epc::SET_PROPERTY_MAP, epc::GET_PROPERTY_MAP])?;
+    let res = client.request(addr, packet).await?;
+    let (Some(set), Some(get)) = (
+        res.get_prop(epc::SET_PROPERTY_MAP).filter(|p| p.pdc.0 > 0),
+        res.get_prop(epc::GET_PROPERTY_MAP).filter(|p| p.pdc.0 > 0),
+    ) else {
+        anyhow::bail!("not found property maps");
+    };
+    let gettable = parse_property_map(&get.edt)?;
+    let mut epcs = vec![];
+    for epc in parse_property_map(&set.edt)? {
+        if EXCLUDED_EPCS.contains(&epc) {
+            continue;
+        }
+        if gettable.contains(&epc) {
+            epcs.push(epc);
+        } else {
+            warn!(
+                "[{}] {:?} {:?} is settable but not gettable, skipped",
+                addr, eoj, epc
+            );
+        }
+    }
+    Ok(Snapshot {
+        addr,
+        eoj,
+        taken_at: Timestamp::now(),
+        props: scan::get_props(client, addr, eoj, &epcs).await,
+    })
+}
+
+// writes the properties one by one so that a refused one doesn't prevent the others, then verifies them
+pub async fn restore(
+    client: &Client,
+    output: &Output,
+    addr: IpAddr,
+    snapshot: &Snapshot,
+) -> anyhow::Result<()> {
+    let eoj = snapshot.eoj;
+    let mut accepted = vec![];
+    let mut failures = 0;
+    for (&epc, edt) in &snapshot.props {
+        let result = match Packet::new_set_request(eoj, vec![(epc, edt.clone())]) {
+            Ok(packet) => client.request(addr, packet).await,
+            Err(e) => Err(e),
+        };
+        let result = match result {
+            Ok(res) if res.is_normal_response() => {
+                accepted.push(epc);
+                continue;
+            }
+            Ok(_) => RestoreResult::Refused,
+            Err(e) => {
+                warn!("[{}] Failed to restore {:?} {:?}: {:#}", addr, eoj, epc, e);
+                RestoreResult::Failed
+            }
+        };
+        failures += 1;
+        output.emit(&Event::Restore {
+            addr,
+            eoj,
+            epc,
+            result,
+        });
+    }
+
+    let current = scan::get_props(client, addr, eoj, &accepted).await;
+    for epc in accepted {
+        let result = if current.get(&epc) == snapshot.props.get(&epc) {
+            RestoreResult::Restored
+        } else {
+            failures += 1;
+            RestoreResult::Mismatch
+        };
+        output.emit(&Event::Restore {
+            addr,
+            eoj,
+            epc,
+            result,
+        });
+    }
+    if failures > 0 {
+        anyhow::bail!(
+            "{} of {} properties could not be restored",
+            failures,
+            snapshot.props.len()
+        );
+    }
+    Ok(())
+}
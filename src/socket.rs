@@ -0,0 +1,212 @@
+use crate::{
+    cli::SocketOpts,
+    consts::{ECHONET_LITE_PORT, MULTICAST_ADDR_V4, MULTICAST_ADDR_V6},
+};
+use log::{error, warn};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+use tokio::{net::UdpSocket, sync::mpsc};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Family {
+    V4,
+    V6,
+}
+
+pub struct Sockets {
+    pub unicast: Unicast,
+    // only present in the fallback mode, where the unicast sockets are bound to ephemeral ports
+    pub multicast: Vec<Arc<UdpSocket>>,
+}
+
+// used for sending requests and receiving responses to them, one socket per address family
+#[derive(Clone)]
+pub struct Unicast {
+    pub v4: Arc<UdpSocket>,
+    // absent on hosts without IPv6
+    pub v6: Option<Arc<UdpSocket>>,
+}
+
+impl Unicast {
+    // the socket of the family of the destination
+    pub fn get(&self, addr: &IpAddr) -> io::Result<&UdpSocket> {
+        match addr {
+            IpAddr::V4(_) => Ok(&self.v4),
+            IpAddr::V6(_) => self
+                .v6
+                .as_deref()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "IPv6 is not available")),
+        }
+    }
+
+    // the multicast groups which discovery requests can be sent to
+    pub fn multicast_addrs(&self) -> Vec<IpAddr> {
+        let mut addrs = vec![IpAddr::V4(MULTICAST_ADDR_V4)];
+        if self.v6.is_some() {
+            addrs.push(IpAddr::V6(MULTICAST_ADDR_V6));
+        }
+        addrs
+    }
+}
+
+impl Sockets {
+    pub fn bind(opts: &SocketOpts) -> anyhow::Result<Self> {
+        let (v4, multicast_v4) = bind(Family::V4, opts)?;
+        // IPv6 is optional, as many home networks still run IPv4 only
+        let (v6, multicast_v6) = match bind(Family::V6, opts) {
+            Ok((s, multicast)) => (Some(Arc::new(s)), multicast),
+            Err(e) => {
+                warn!("Failed to open an IPv6 socket, only IPv4 is used: {:?}", e);
+                (None, None)
+            }
+        };
+        Ok(Self {
+            unicast: Unicast {
+                v4: Arc::new(v4),
+                v6,
+            },
+            multicast: multicast_v4
+                .into_iter()
+                .chain(multicast_v6)
+                .map(Arc::new)
+                .collect(),
+        })
+    }
+
+    pub fn local_port(&self) -> io::Result<u16> {
+        Ok(self.unicast.v4.local_addr()?.port())
+    }
+
+    // spawns a receiving task for every socket and merges the received datagrams into one channel
+    pub fn receive(&self) -> mpsc::Receiver<(Vec<u8>, SocketAddr)> {
+        let (tx, rx) = mpsc::channel(64);
+        let sockets = std::iter::once(&self.unicast.v4)
+            .chain(self.unicast.v6.as_ref())
+            .chain(&self.multicast);
+        for sock in sockets {
+            let sock = Arc::clone(sock);
+            let tx = tx.clone();
+            tokio::spawn(async move {
+                let mut buf = [0; 1024];
+                loop {
+                    match sock.recv_from(&mut buf).await {
+                        Ok((len, addr)) => {
+                            if tx.send((buf[..len].to_vec(), addr)).await.is_err() {
+                                return;
+                            }
+                        }
+                        Err(e) => error!("Failed to receive a packet: {:?}", e),
+                    }
+                }
+            });
+        }
+        rx
+    }
+}
+
+// binds the ECHONET Lite port for both unicast and multicast, or in the fallback mode an ephemeral port
+// for unicast and the ECHONET Lite port shared with the other controller for multicast
+fn bind(family: Family, opts: &SocketOpts) -> io::Result<(UdpSocket, Option<UdpSocket>)> {
+    match open(family, ECHONET_LITE_PORT, opts.reuse_addr, opts.reuse_port) {
+        Ok(s) => {
+            join(family, &s)?;
+            Ok((s, None))
+        }
+        Err(e) if opts.fallback && e.kind() == io::ErrorKind::AddrInUse => {
+            warn!(
+                "Port {} ({:?}) is already in use, falling back to an ephemeral port for unicast requests",
+                ECHONET_LITE_PORT, family
+            );
+            let unicast = open(family, 0, false, false)?;
+            // sharing the port succeeds only if the other controller also enabled address/port reuse
+            let shared = open(family, ECHONET_LITE_PORT, true, true)
+                .and_then(|s| join(family, &s).map(|_| s));
+            let multicast = match shared {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    warn!(
+                        "Failed to share port {} ({:?}), multicast packets will not be received: {:?}",
+                        ECHONET_LITE_PORT, family, e
+                    );
+                    None
+                }
+            };
+            Ok((unicast, multicast))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn open(family: Family, port: u16, reuse_addr: bool, reuse_port: bool) -> io::Result<UdpSocket> {
+    let (domain, addr) = match family {
+        Family::V4 => (
+            Domain::IPV4,
+            SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)),
+        ),
+        Family::V6 => (
+            Domain::IPV6,
+            SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)),
+        ),
+    };
+    let s = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+    // the IPv4 socket is bound to the same port
+    if family == Family::V6 {
+        s.set_only_v6(true)?;
+    }
+    if reuse_addr {
+        s.set_reuse_address(true)?;
+    }
+    if reuse_port {
+        #[cfg(unix)]
+        s.set_reuse_port(true)?;
+        #[cfg(not(unix))]
+        warn!("SO_REUSEPORT is not supported on this platform");
+    }
+    s.set_nonblocking(true)?;
+    s.bind(&addr.into())?;
+    UdpSocket::from_std(s.into())
+}
+
+fn join(family: Family, s: &UdpSocket) -> io::Result<()> {
+    match family {
+        Family::V4 => {
+            s.set_multicast_loop_v4(false)?;
+            s.join_multicast_v4(MULTICAST_ADDR_V4, Ipv4Addr::UNSPECIFIED)
+        }
+        Family::V6 => {
+            s.set_multicast_loop_v6(false)?;
+            s.join_multicast_v6(&MULTICAST_ADDR_V6, 0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // on an ephemeral port, as the ECHONET Lite one may be taken. it needs a route for the group,
+    // which sandboxes lack at times, so it's run by the CI of the musl targets with --ignored
+    #[tokio::test]
+    #[ignore]
+    async fn test_join_multicast() {
+        let s = open(Family::V4, 0, true, true).unwrap();
+        join(Family::V4, &s).unwrap();
+        let port = s.local_addr().unwrap().port();
+        let sender = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+        sender
+            .send_to(b"ping", (MULTICAST_ADDR_V4, port))
+            .await
+            .unwrap();
+        let mut buf = [0; 16];
+        let (len, _) = tokio::time::timeout(Duration::from_secs(1), s.recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..len], b"ping");
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+// a file of the records a sink failed to write, each prefixed with its length in 4 bytes, which are
+// written out in order once the destination is back. it survives restarts, and a record is written
+// twice at worst when the process dies while replaying
+pub struct Spool {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    records: u64,
+}
+
+impl Spool {
+    // continues the records left by the previous run, up to max_size bytes
+    pub fn open(path: &Path, max_size: u64) -> io::Result<Self> {
+        let mut records = 0;
+        let mut size = 0;
+        if let Ok(f) = File::open(path) {
+            let mut reader = BufReader::new(f);
+            while let Some(record) = read_record(&mut reader, max_size)? {
+                records += 1;
+                size += 4 + record.len() as u64;
+            }
+        }
+        // drops a record cut off by a crash
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.set_len(size)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            size,
+            max_size,
+            records,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records == 0
+    }
+
+    pub fn records(&self) -> u64 {
+        self.records
+    }
+
+    // appends the record, returning false if the spool is full
+    pub fn push(&mut self, record: &[u8]) -> io::Result<bool> {
+        let size = 4 + record.len() as u64;
+        if self.size + size > self.max_size {
+            return Ok(false);
+        }
+        let mut buf = Vec::with_capacity(size as usize);
+        buf.extend_from_slice(&(record.len() as u32).to_be_bytes());
+        buf.extend_from_slice(record);
+        self.file.write_all(&buf)?;
+        self.size += size;
+        self.records += 1;
+        Ok(true)
+    }
+
+    // writes the records out from the oldest, keeping the rest from the first one which fails, and
+    // returns the number of the records written
+    pub fn replay(&mut self, w: &mut impl Write) -> io::Result<u64> {
+        let mut reader = BufReader::new(File::open(&self.path)?);
+        let mut written = 0;
+        let mut rest = None;
+        while let Some(record) = read_record(&mut reader, self.max_size)? {
+            if w.write_all(&record).and_then(|_| w.flush()).is_err() {
+                rest = Some(record);
+                break;
+            }
+            written += 1;
+        }
+        match rest {
+            _ if written == 0 => {}
+            None => {
+                self.file.set_len(0)?;
+                self.size = 0;
+                self.records = 0;
+            }
+            Some(first) => {
+                // rewritten rather than keeping an offset, so that the next run starts from the rest
+                let tmp = self.path.with_extension("tmp");
+                let mut out = BufWriter::new(File::create(&tmp)?);
+                out.write_all(&(first.len() as u32).to_be_bytes())?;
+                out.write_all(&first)?;
+                io::copy(&mut reader, &mut out)?;
+                out.into_inner().map_err(|e| e.into_error())?.sync_data()?;
+                fs::rename(&tmp, &self.path)?;
+                self.file = OpenOptions::new().append(true).open(&self.path)?;
+                self.size = self.file.metadata()?.len();
+                self.records -= written;
+            }
+        }
+        Ok(written)
+    }
+}
+
+// a length no record can have is taken for a record cut off, rather than allocated
+fn read_record(reader: &mut impl Read, max_size: u64) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes(len) as u64;
+    if 4 + len > max_size {
+        return Ok(None);
+    }
+    let mut record = vec![0; len as usize];
+    match reader.read_exact(&mut record) {
+        Ok(()) => Ok(Some(record)),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // accepts the given number of writes
+    struct Flaky {
+        accepts: usize,
+        written: Vec<u8>,
+    }
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.accepts == 0 {
+                return Err(io::Error::new(io::ErrorKind::NotConnected, "down"));
+            }
+            self.accepts -= 1;
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_spool() {
+        let path = std::env::temp_dir().join(format!("elscan-{}.spool", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut spool = Spool::open(&path, 20).unwrap();
+        assert!(spool.push(b"first").unwrap());
+        assert!(spool.push(b"second").unwrap());
+        // 9 + 10 bytes are taken
+        assert!(!spool.push(b"third").unwrap());
+        let mut w = Flaky {
+            accepts: 1,
+            written: vec![],
+        };
+        assert_eq!(spool.replay(&mut w).unwrap(), 1);
+        assert_eq!(spool.records(), 1);
+
+        // a record cut off by a crash
+        spool.file.write_all(&[0, 0, 0, 9, b'x']).unwrap();
+        let mut spool = Spool::open(&path, 20).unwrap();
+        assert_eq!(spool.records(), 1);
+        assert!(spool.push(b"third").unwrap());
+        w.accepts = 2;
+        assert_eq!(spool.replay(&mut w).unwrap(), 2);
+        assert!(spool.is_empty());
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(w.written, b"firstsecondthird");
+
+        // a corrupted length
+        fs::write(&path, [0xFF, 0xFF, 0xFF, 0xFF, b'x']).unwrap();
+        let spool = Spool::open(&path, 20).unwrap();
+        assert!(spool.is_empty());
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,538 @@
+use crate::{
+    aggregate::{Aggregator, HouseholdPower},
+    client::Client,
+    output::{Event, Output},
+    packet::ESV,
+};
+use log::{debug, info};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    time::{self, Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ParseError {
+    // shorter than the header, or than the properties it declares
+    Truncated,
+    UnknownEsv,
+    // the other malformed frames, such as the ones of other protocols
+    Malformed,
+}
+
+impl ParseError {
+    // tells why the frame couldn't be parsed, by the fields the parser checks in order
+    pub fn classify(msg: &[u8]) -> Self {
+        match msg {
+            [0x10, 0x81, ..] if msg.len() < 12 => Self::Truncated,
+            [0x10, 0x81, _, _, _, _, _, _, _, _, esv, ..] if ESV::try_from(*esv).is_err() => {
+                Self::UnknownEsv
+            }
+            [0x10, 0x81, ..] => Self::Truncated,
+            _ => Self::Malformed,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Truncated => "truncated",
+            Self::UnknownEsv => "unknown_esv",
+            Self::Malformed => "malformed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Counters {
+    pub sent: BTreeMap<ESV, u64>,
+    pub received: BTreeMap<ESV, u64>,
+    // packets of any kind per source, including the ones which couldn't be parsed
+    pub sources: BTreeMap<IpAddr, u64>,
+    pub parse_errors: BTreeMap<ParseError, u64>,
+    // packets sent from other ports than the ECHONET Lite port, which the specification requires
+    pub nonstandard_ports: BTreeMap<(IpAddr, u16), u64>,
+    // packets dropped as their sources exceeded the inbound rate limit
+    pub muted: BTreeMap<IpAddr, u64>,
+}
+
+// counts the packets the client sends and receives
+#[derive(Default)]
+pub struct Stats {
+    counters: Mutex<Counters>,
+}
+
+impl Stats {
+    pub fn sent(&self, esv: ESV) {
+        *self.counters.lock().unwrap().sent.entry(esv).or_default() += 1;
+    }
+
+    pub fn received(&self, addr: IpAddr, esv: Option<ESV>) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.sources.entry(addr).or_default() += 1;
+        if let Some(esv) = esv {
+            *counters.received.entry(esv).or_default() += 1;
+        }
+    }
+
+    pub fn parse_error(&self, addr: IpAddr, error: ParseError) {
+        let mut counters = self.counters.lock().unwrap();
+        *counters.sources.entry(addr).or_default() += 1;
+        *counters.parse_errors.entry(error).or_default() += 1;
+    }
+
+    // returns how many packets have been received from the port so far
+    pub fn nonstandard_port(&self, addr: IpAddr, port: u16) -> u64 {
+        let mut counters = self.counters.lock().unwrap();
+        let n = counters.nonstandard_ports.entry((addr, port)).or_default();
+        *n += 1;
+        *n
+    }
+
+    pub fn muted(&self, addr: IpAddr) {
+        *self.counters.lock().unwrap().muted.entry(addr).or_default() += 1;
+    }
+
+    pub fn snapshot(&self) -> Counters {
+        self.counters.lock().unwrap().clone()
+    }
+}
+
+fn metric(s: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(s, "# HELP elscan_{} {}", name, help).unwrap();
+    writeln!(s, "# TYPE elscan_{} {}", name, kind).unwrap();
+}
+
+// renders the statistics of the client and the output in the Prometheus text exposition format
+pub fn render_prometheus(client: &Client, output: &Output, aggregator: &Aggregator) -> String {
+    let counters = client.stats().snapshot();
+    let mut s = String::new();
+    metric(
+        &mut s,
+        "packets_sent_total",
+        "counter",
+        "Packets sent by ESV.",
+    );
+    for (esv, n) in &counters.sent {
+        writeln!(s, "elscan_packets_sent_total{{esv=\"{:?}\"}} {}", esv, n).unwrap();
+    }
+    metric(
+        &mut s,
+        "packets_received_total",
+        "counter",
+        "Packets received by ESV.",
+    );
+    for (esv, n) in &counters.received {
+        writeln!(
+            s,
+            "elscan_packets_received_total{{esv=\"{:?}\"}} {}",
+            esv, n
+        )
+        .unwrap();
+    }
+    metric(
+        &mut s,
+        "source_packets_total",
+        "counter",
+        "Packets received by source address.",
+    );
+    for (addr, n) in &counters.sources {
+        writeln!(s, "elscan_source_packets_total{{addr=\"{}\"}} {}", addr, n).unwrap();
+    }
+    metric(
+        &mut s,
+        "parse_errors_total",
+        "counter",
+        "Packets which could not be parsed.",
+    );
+    for error in [
+        ParseError::Truncated,
+        ParseError::UnknownEsv,
+        ParseError::Malformed,
+    ] {
+        let n = counters
+            .parse_errors
+            .get(&error)
+            .copied()
+            .unwrap_or_default();
+        writeln!(
+            s,
+            "elscan_parse_errors_total{{kind=\"{}\"}} {}",
+            error.label(),
+            n
+        )
+        .unwrap();
+    }
+    metric(
+        &mut s,
+        "nonstandard_port_packets_total",
+        "counter",
+        "Packets received from other ports than 3610 by source address and port.",
+    );
+    for ((addr, port), n) in &counters.nonstandard_ports {
+        writeln!(
+            s,
+            "elscan_nonstandard_port_packets_total{{addr=\"{}\",port=\"{}\"}} {}",
+            addr, port, n
+        )
+        .unwrap();
+    }
+    metric(
+        &mut s,
+        "muted_packets_total",
+        "counter",
+        "Packets dropped by source address as the source exceeded the rate limit.",
+    );
+    for (addr, n) in &counters.muted {
+        writeln!(s, "elscan_muted_packets_total{{addr=\"{}\"}} {}", addr, n).unwrap();
+    }
+    metric(
+        &mut s,
+        "arbitrary_frames_total",
+        "counter",
+        "Arbitrary message format frames received by source address.",
+    );
+    for (addr, n) in client
+        .arbitrary_frames()
+        .into_iter()
+        .collect::<BTreeMap<_, _>>()
+    {
+        writeln!(
+            s,
+            "elscan_arbitrary_frames_total{{addr=\"{}\"}} {}",
+            addr, n
+        )
+        .unwrap();
+    }
+    metric(
+        &mut s,
+        "stale_responses_total",
+        "counter",
+        "Responses which arrived after their requests timed out.",
+    );
+    writeln!(
+        s,
+        "elscan_stale_responses_total {}",
+        client.stale_responses()
+    )
+    .unwrap();
+
+    let pool = client.pool_stats();
+    metric(
+        &mut s,
+        "outstanding_requests",
+        "gauge",
+        "Requests waiting for responses.",
+    );
+    writeln!(s, "elscan_outstanding_requests {}", pool.outstanding).unwrap();
+    metric(
+        &mut s,
+        "peak_outstanding_requests",
+        "gauge",
+        "Most requests waiting for responses at a time.",
+    );
+    writeln!(
+        s,
+        "elscan_peak_outstanding_requests {}",
+        pool.peak_outstanding
+    )
+    .unwrap();
+    metric(
+        &mut s,
+        "max_outstanding_requests",
+        "gauge",
+        "Requests allowed to wait for responses at a time.",
+    );
+    writeln!(
+        s,
+        "elscan_max_outstanding_requests {}",
+        pool.max_outstanding
+    )
+    .unwrap();
+    metric(
+        &mut s,
+        "queued_requests_total",
+        "counter",
+        "Requests which had to wait for a slot in the pool.",
+    );
+    writeln!(s, "elscan_queued_requests_total {}", pool.queued).unwrap();
+
+    let sinks = output.sink_stats();
+    metric(
+        &mut s,
+        "sink_queued_records",
+        "gauge",
+        "Records waiting to be written by sink.",
+    );
+    for sink in &sinks {
+        writeln!(
+            s,
+            "elscan_sink_queued_records{{sink=\"{}\"}} {}",
+            sink.name, sink.queued
+        )
+        .unwrap();
+    }
+    metric(
+        &mut s,
+        "sink_written_records_total",
+        "counter",
+        "Records written by sink.",
+    );
+    for sink in &sinks {
+        writeln!(
+            s,
+            "elscan_sink_written_records_total{{sink=\"{}\"}} {}",
+            sink.name, sink.written
+        )
+        .unwrap();
+    }
+    metric(
+        &mut s,
+        "sink_dropped_records_total",
+        "counter",
+        "Records dropped by sink as its queue or its spool was full.",
+    );
+    for sink in &sinks {
+        writeln!(
+            s,
+            "elscan_sink_dropped_records_total{{sink=\"{}\"}} {}",
+            sink.name, sink.dropped
+        )
+        .unwrap();
+    }
+    metric(
+        &mut s,
+        "sink_spooled_records",
+        "gauge",
+        "Records kept on disk by sink until its destination is back.",
+    );
+    for sink in &sinks {
+        writeln!(
+            s,
+            "elscan_sink_spooled_records{{sink=\"{}\"}} {}",
+            sink.name, sink.spooled
+        )
+        .unwrap();
+    }
+    if let Some(power) = aggregator.total() {
+        render_household(&mut s, &power);
+    }
+    match output.gateway() {
+        Some(gateway) => with_gateway(&s, gateway),
+        None => s,
+    }
+}
+
+// the grid and the consumption are known only when a smart meter is found
+fn render_household(s: &mut String, power: &HouseholdPower) {
+    let gauges = [
+        (
+            "grid",
+            power.grid,
+            "Power bought from the grid, negative while selling.",
+        ),
+        ("generation", Some(power.generation), "Power generated."),
+        (
+            "storage",
+            Some(power.storage),
+            "Power charged to the batteries, negative while discharging.",
+        ),
+        (
+            "consumption",
+            power.consumption,
+            "Power consumed by the household.",
+        ),
+    ];
+    for (name, watts, help) in gauges {
+        if let Some(watts) = watts {
+            let name = format!("household_{}_watts", name);
+            metric(s, &name, "gauge", help);
+            writeln!(s, "elscan_{} {}", name, watts).unwrap();
+        }
+    }
+}
+
+// labels every sample with the gateway, for the collectors scraping many gateways
+fn with_gateway(metrics: &str, gateway: &str) -> String {
+    let mut s = String::new();
+    for line in metrics.lines() {
+        if line.starts_with('#') {
+            writeln!(s, "{}", line).unwrap();
+        } else if let Some((name, labels)) = line.split_once('{') {
+            writeln!(s, "{}{{gateway=\"{}\",{}", name, gateway, labels).unwrap();
+        } else if let Some((name, value)) = line.split_once(' ') {
+            writeln!(s, "{}{{gateway=\"{}\"}} {}", name, gateway, value).unwrap();
+        }
+    }
+    s
+}
+
+// emits a heartbeat right away and then at the interval, until the process exits
+pub async fn heartbeat(client: Arc<Client>, output: Arc<Output>, interval: Duration) {
+    let started = Instant::now();
+    let mut ticker = time::interval(interval);
+    ticker.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let counters = client.stats().snapshot();
+        output.emit(&Event::Heartbeat {
+            uptime: started.elapsed().as_secs(),
+            packets_sent: counters.sent.values().sum(),
+            packets_received: counters.received.values().sum(),
+            sources: counters.sources.len(),
+            dropped_records: output.sink_stats().iter().map(|s| s.dropped).sum(),
+        });
+    }
+}
+
+// answers GET /metrics with a plain HTTP/1.0 response, enough for the Prometheus scraper
+pub async fn serve(
+    client: Arc<Client>,
+    output: Arc<Output>,
+    aggregator: Arc<Aggregator>,
+    listener: TcpListener,
+) -> anyhow::Result<()> {
+    info!(
+        "Serving metrics at http://{}/metrics",
+        listener.local_addr()?
+    );
+    loop {
+        let (mut stream, peer) = listener.accept().await?;
+        let (client, output, aggregator) = (
+            Arc::clone(&client),
+            Arc::clone(&output),
+            Arc::clone(&aggregator),
+        );
+        tokio::spawn(async move {
+            let mut buf = [0; 1024];
+            let n = match stream.read(&mut buf).await {
+                Ok(n) => n,
+                Err(e) => {
+                    debug!("[{}] Failed to read a metrics request: {:?}", peer, e);
+                    return;
+                }
+            };
+            let request = String::from_utf8_lossy(&buf[..n]);
+            let response = match request.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+                ["GET", "/metrics"] => {
+                    let body = render_prometheus(&client, &output, &aggregator);
+                    format!(
+                        "HTTP/1.0 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                }
+                _ => "HTTP/1.0 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_string(),
+            };
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                debug!("[{}] Failed to write a metrics response: {:?}", peer, e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            ParseError::classify(&[0x10, 0x81, 0x00, 0x01]),
+            ParseError::Truncated
+        );
+        let frame = [
+            0x10, 0x81, 0x00, 0x01, 0x05, 0xFF, 0x01, 0x01, 0x30, 0x01, 0x99, 0x00,
+        ];
+        assert_eq!(ParseError::classify(&frame), ParseError::UnknownEsv);
+        // declares a property which isn't there
+        let frame = [
+            0x10, 0x81, 0x00, 0x01, 0x05, 0xFF, 0x01, 0x01, 0x30, 0x01, 0x72, 0x01, 0x80,
+        ];
+        assert_eq!(ParseError::classify(&frame), ParseError::Truncated);
+        assert_eq!(
+            ParseError::classify(b"GET / HTTP/1.1\r\n"),
+            ParseError::Malformed
+        );
+    }
+
+    #[test]
+    fn test_counters() {
+        let stats = Stats::default();
+        let addr: IpAddr = "192.168.1.10".parse().unwrap();
+        stats.sent(ESV::Get);
+        stats.received(addr, Some(ESV::GetRes));
+        stats.received(addr, Some(ESV::Inf));
+        stats.received(addr, None);
+        stats.parse_error(addr, ParseError::Truncated);
+        stats.muted(addr);
+        assert_eq!(stats.nonstandard_port(addr, 50000), 1);
+        assert_eq!(stats.nonstandard_port(addr, 50000), 2);
+        let counters = stats.snapshot();
+        assert_eq!(counters.sent, BTreeMap::from([(ESV::Get, 1)]));
+        assert_eq!(
+            counters.received,
+            BTreeMap::from([(ESV::GetRes, 1), (ESV::Inf, 1)])
+        );
+        assert_eq!(counters.sources, BTreeMap::from([(addr, 4)]));
+        assert_eq!(
+            counters.parse_errors,
+            BTreeMap::from([(ParseError::Truncated, 1)])
+        );
+        assert_eq!(
+            counters.nonstandard_ports,
+            BTreeMap::from([((addr, 50000), 2)])
+        );
+        assert_eq!(counters.muted, BTreeMap::from([(addr, 1)]));
+    }
+
+    #[test]
+    fn test_render_household() {
+        let mut s = String::new();
+        render_household(
+            &mut s,
+            &HouseholdPower {
+                grid: None,
+                generation: 3000.0,
+                storage: -500.0,
+                consumption: None,
+            },
+        );
+        assert!(!s.contains("grid") && !s.contains("consumption"));
+        assert!(s.contains("# TYPE elscan_household_generation_watts gauge\n"));
+        assert!(s.contains("\nelscan_household_generation_watts 3000\n"));
+        assert!(s.contains("\nelscan_household_storage_watts -500\n"));
+        let mut s = String::new();
+        render_household(
+            &mut s,
+            &HouseholdPower {
+                grid: Some(-1000.0),
+                generation: 3000.0,
+                storage: 0.0,
+                consumption: Some(2000.0),
+            },
+        );
+        assert!(s.contains("\nelscan_household_grid_watts -1000\n"));
+        assert!(s.contains("\nelscan_household_consumption_watts 2000\n"));
+    }
+
+    #[test]
+    fn test_with_gateway() {
+        let metrics = "# HELP elscan_stale_responses_total Late responses.\n\
+            # TYPE elscan_stale_responses_total counter\n\
+            elscan_stale_responses_total 3\n\
+            elscan_packets_sent_total{esv=\"Get\"} 12\n";
+        assert_eq!(
+            with_gateway(metrics, "gw-1"),
+            "# HELP elscan_stale_responses_total Late responses.\n\
+            # TYPE elscan_stale_responses_total counter\n\
+            elscan_stale_responses_total{gateway=\"gw-1\"} 3\n\
+            elscan_packets_sent_total{gateway=\"gw-1\",esv=\"Get\"} 12\n"
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    client::Client,
+    consts::{eoj, epc},
+    output::{Event, Output},
+    packet::Packet,
+    response::DiscoveryResponse,
+};
+use log::{debug, info};
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::Semaphore,
+    task::JoinSet,
+    time::{self, MissedTickBehavior},
+};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+// asks every address for the instance list of its node by unicast, for networks where multicast doesn't reach
+pub async fn run(
+    client: Arc<Client>,
+    output: Arc<Output>,
+    hosts: Vec<IpAddr>,
+    parallelism: usize,
+    timeout: Duration,
+) -> anyhow::Result<()> {
+    let total = hosts.len();
+    let done = Arc::new(AtomicUsize::new(0));
+    let found = Arc::new(AtomicUsize::new(0));
+    let progress = {
+        let (done, found) = (Arc::clone(&done), Arc::clone(&found));
+        tokio::spawn(async move {
+            let mut interval = time::interval(PROGRESS_INTERVAL);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                info!(
+                    "Swept {}/{} addresses, {} nodes found",
+                    done.load(Ordering::Relaxed),
+                    total,
+                    found.load(Ordering::Relaxed)
+                );
+            }
+        })
+    };
+
+    info!(
+        "Sweeping {} addresses ({} at a time, {:?} timeout)",
+        total, parallelism, timeout
+    );
+    let permits = Arc::new(Semaphore::new(parallelism));
+    let mut tasks = JoinSet::new();
+    for addr in hosts {
+        // the tasks are spawned as permits are available so that large sweeps don't pile them up
+        let permit = Arc::clone(&permits).acquire_owned().await?;
+        let (client, output) = (Arc::clone(&client), Arc::clone(&output));
+        let (done, found) = (Arc::clone(&done), Arc::clone(&found));
+        tasks.spawn(async move {
+            let packet =
+                Packet::new_get_request(eoj::NODE_PROFILE, &[epc::INSTANCE_LIST_S]).unwrap();
+            let result = client.request_within(addr, packet, timeout).await;
+            match result.and_then(|res| DiscoveryResponse::try_from(&res)) {
+                Ok(response) => {
+                    found.fetch_add(1, Ordering::Relaxed);
+                    output.emit(&Event::Discovery { addr, response });
+                }
+                // most of the addresses are expected to be silent
+                Err(e) => debug!("[{}] No node found: {:?}", addr, e),
+            }
+            done.fetch_add(1, Ordering::Relaxed);
+            drop(permit);
+        });
+        // reaps the finished tasks as it goes
+        while tasks.try_join_next().is_some() {}
+    }
+    while tasks.join_next().await.is_some() {}
+    progress.abort();
+    info!(
+        "Swept {} addresses, {} nodes found",
+        total,
+        found.load(Ordering::Relaxed)
+    );
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use crate::{
+    consts::epc::{self, home_air_conditioner, smart_meter},
+    decoder,
+    packet::ElU8,
+};
+use clap::ValueEnum;
+use std::time::Duration;
+
+// built-in polling schedules of the common devices, each for one class
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Template {
+    // the instantaneous power every 10 seconds and the cumulative energy every 30 minutes
+    SmartMeterDefault,
+    // the operation status, the room temperature and the set temperature every minute
+    AirconDefault,
+}
+
+const SMART_METER_DEFAULT: &[(Duration, &[ElU8])] = &[
+    (Duration::from_secs(10), &[smart_meter::INSTANTANEOUS_POWER]),
+    (
+        Duration::from_secs(30 * 60),
+        &[smart_meter::CUMULATIVE_ENERGY],
+    ),
+];
+const AIRCON_DEFAULT: &[(Duration, &[ElU8])] = &[(
+    Duration::from_secs(60),
+    &[
+        epc::OPERATION_STATUS,
+        home_air_conditioner::ROOM_TEMPERATURE,
+        home_air_conditioner::SET_TEMPERATURE,
+    ],
+)];
+
+impl Template {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SmartMeterDefault => "smart-meter-default",
+            Self::AirconDefault => "aircon-default",
+        }
+    }
+
+    pub fn class(self) -> u16 {
+        match self {
+            Self::SmartMeterDefault => decoder::SMART_METER,
+            Self::AirconDefault => decoder::HOME_AIR_CONDITIONER,
+        }
+    }
+
+    // the properties read together at each interval
+    pub fn schedule(self) -> &'static [(Duration, &'static [ElU8])] {
+        match self {
+            Self::SmartMeterDefault => SMART_METER_DEFAULT,
+            Self::AirconDefault => AIRCON_DEFAULT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the names are given on the command line
+    #[test]
+    fn test_name() {
+        for template in Template::value_variants() {
+            assert_eq!(
+                template.to_possible_value().unwrap().get_name(),
+                template.name()
+            );
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    time::{Duration, Instant},
+};
+
+// TIDs of the timed-out requests are not reused for this while, so that late responses to them are
+// recognized rather than taken for the responses to new requests
+pub const EXPIRED_TID_RETENTION: Duration = Duration::from_secs(30);
+
+// what a response is to the requests, by its source address and TID
+#[derive(Debug, PartialEq)]
+pub enum Matched<W> {
+    // the waiter of the outstanding request it answers
+    Pending(W),
+    // answers a request which already timed out
+    Stale,
+    Unexpected,
+}
+
+// keeps track of the TIDs of the requests independently of how the responses are waited for
+pub struct Transactions<W> {
+    last_tid: u16,
+    pending: HashMap<u16, (IpAddr, W)>,
+    // the requests to all the instances of a class, which are answered by each of them
+    broadcasts: HashSet<u16>,
+    expired: HashMap<u16, (IpAddr, Instant)>,
+}
+
+impl<W> Default for Transactions<W> {
+    fn default() -> Self {
+        Self {
+            last_tid: 0,
+            pending: HashMap::new(),
+            broadcasts: HashSet::new(),
+            expired: HashMap::new(),
+        }
+    }
+}
+
+impl<W> Transactions<W> {
+    // the next TID neither outstanding nor recently expired, wrapping around at 16 bits and skipping 0,
+    // which devices often use for their own notifications
+    pub fn allocate(&mut self, now: Instant) -> anyhow::Result<u16> {
+        self.expired
+            .retain(|_, (_, at)| now.duration_since(*at) < EXPIRED_TID_RETENTION);
+        for _ in 0..u16::MAX {
+            self.last_tid = self.last_tid.wrapping_add(1);
+            if self.last_tid == 0 {
+                continue;
+            }
+            if !self.pending.contains_key(&self.last_tid)
+                && !self.expired.contains_key(&self.last_tid)
+            {
+                return Ok(self.last_tid);
+            }
+        }
+        anyhow::bail!("no TID available")
+    }
+
+    // allocates a TID for a request to the address
+    pub fn begin(&mut self, addr: IpAddr, waiter: W, now: Instant) -> anyhow::Result<u16> {
+        let tid = self.allocate(now)?;
+        self.pending.insert(tid, (addr, waiter));
+        Ok(tid)
+    }
+
+    // allocates a TID for a request to instance 0 of a class, whose responses all go to the waiter
+    // until the request ends
+    pub fn begin_broadcast(
+        &mut self,
+        addr: IpAddr,
+        waiter: W,
+        now: Instant,
+    ) -> anyhow::Result<u16> {
+        let tid = self.begin(addr, waiter, now)?;
+        self.broadcasts.insert(tid);
+        Ok(tid)
+    }
+
+    // forgets the request, keeping its TID from being reused for a while if it wasn't answered
+    pub fn end(&mut self, addr: IpAddr, tid: u16, answered: bool, now: Instant) {
+        self.pending.remove(&tid);
+        self.broadcasts.remove(&tid);
+        if !answered {
+            self.expired.insert(tid, (addr, now));
+        }
+    }
+}
+
+impl<W> Transactions<W> {
+    // whether a response from the address with the TID would answer an outstanding request
+    pub fn is_pending(&self, addr: IpAddr, tid: u16) -> bool {
+        matches!(self.pending.get(&tid), Some((to, _)) if *to == addr || to.is_multicast())
+    }
+}
+
+impl<W: Clone> Transactions<W> {
+    // responses only match the requests sent to the address they come from, or to a multicast group,
+    // which every node in it answers
+    pub fn resolve(&mut self, addr: IpAddr, tid: u16) -> Matched<W> {
+        if self.is_pending(addr, tid) {
+            if self.broadcasts.contains(&tid) {
+                return Matched::Pending(self.pending[&tid].1.clone());
+            }
+            let (_, waiter) = self.pending.remove(&tid).unwrap();
+            return Matched::Pending(waiter);
+        }
+        match self.expired.get(&tid) {
+            Some((to, _)) if *to == addr => Matched::Stale,
+            _ => Matched::Unexpected,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocate_tid() {
+        let now = Instant::now();
+        let addr: IpAddr = "192.168.1.10".parse().unwrap();
+        let mut transactions = Transactions::default();
+        assert_eq!(transactions.allocate(now).unwrap(), 1);
+        assert_eq!(transactions.allocate(now).unwrap(), 2);
+
+        // outstanding and recently expired TIDs are skipped after wrapping around
+        transactions.last_tid = 0xFFFE;
+        transactions.pending.insert(0xFFFF, (addr, ()));
+        transactions.expired.insert(1, (addr, now));
+        assert_eq!(transactions.allocate(now).unwrap(), 2);
+
+        // until they are retained no longer
+        transactions.last_tid = 0;
+        let later = now + EXPIRED_TID_RETENTION;
+        assert_eq!(transactions.allocate(later).unwrap(), 1);
+        assert!(transactions.expired.is_empty());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let now = Instant::now();
+        let addr: IpAddr = "192.168.1.10".parse().unwrap();
+        let other: IpAddr = "192.168.1.11".parse().unwrap();
+        let mut transactions = Transactions::default();
+        let tid = transactions.begin(addr, "waiter", now).unwrap();
+        assert_eq!(transactions.resolve(other, tid), Matched::Unexpected);
+        assert_eq!(transactions.resolve(addr, tid), Matched::Pending("waiter"));
+        // a response is delivered only once
+        assert_eq!(transactions.resolve(addr, tid), Matched::Unexpected);
+        transactions.end(addr, tid, true, now);
+
+        let tid = transactions.begin(addr, "waiter", now).unwrap();
+        transactions.end(addr, tid, false, now);
+        assert_eq!(transactions.resolve(addr, tid), Matched::Stale);
+        assert_eq!(transactions.resolve(other, tid), Matched::Unexpected);
+    }
+
+    #[test]
+    fn test_resolve_broadcast() {
+        let now = Instant::now();
+        let addr: IpAddr = "192.168.1.10".parse().unwrap();
+        let mut transactions = Transactions::default();
+        let tid = transactions.begin_broadcast(addr, "waiter", now).unwrap();
+        // every instance answers
+        assert_eq!(transactions.resolve(addr, tid), Matched::Pending("waiter"));
+        assert_eq!(transactions.resolve(addr, tid), Matched::Pending("waiter"));
+        transactions.end(addr, tid, true, now);
+        assert_eq!(transactions.resolve(addr, tid), Matched::Unexpected);
+
+        // a TID reused for a unicast request is answered once
+        transactions.last_tid = tid - 1;
+        assert_eq!(transactions.begin(addr, "other", now).unwrap(), tid);
+        assert_eq!(transactions.resolve(addr, tid), Matched::Pending("other"));
+        assert_eq!(transactions.resolve(addr, tid), Matched::Unexpected);
+
+        let group: IpAddr = "224.0.23.0".parse().unwrap();
+        let tid = transactions
+            .begin_broadcast(group, "multicast", now)
+            .unwrap();
+        assert_eq!(
+            transactions.resolve(addr, tid),
+            Matched::Pending("multicast")
+        );
+        let other: IpAddr = "192.168.1.11".parse().unwrap();
+        assert_eq!(
+            transactions.resolve(other, tid),
+            Matched::Pending("multicast")
+        );
+    }
+}